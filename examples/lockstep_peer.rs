@@ -0,0 +1,64 @@
+//! A headless lockstep peer, for checking that networked rounds stay in sync without opening a
+//! window. Run one of these to host and another to join, on the same machine or across a LAN:
+//!
+//! ```text
+//! cargo run --example lockstep_peer -- host 7777
+//! cargo run --example lockstep_peer -- join 127.0.0.1:7777
+//! ```
+//!
+//! Each peer steers its snek at random for a while, then prints the tick it got to and a hash of
+//! its world. Peers that stayed in sync print the same thing.
+
+use snek::level::Level;
+use snek::lockstep::Lockstep;
+use snek::snek::Direction;
+use snek::world::{random_seed, Rules, World};
+
+use rand::Rng;
+use std::thread::sleep;
+use std::time::Duration;
+
+static TICKS: u64 = 1200;
+
+fn main() {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let (mut lockstep, mut world) = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+    ["host", port] => {
+      let world = World::new(Level::empty(40, 30), Rules::default(), 2, random_seed());
+      Lockstep::host(port.parse().expect("not a port"), &world)
+    }
+    ["join", address] => Lockstep::join(address),
+    _ => {
+      eprintln!("usage: lockstep_peer host <port> | join <address>:<port>");
+      std::process::exit(1);
+    }
+  }
+  .expect("couldn't connect");
+
+  let directions = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+  ];
+  let mut rng = rand::thread_rng();
+  while world.ticks < TICKS && lockstep.desync.is_none() && !lockstep.timed_out() {
+    if rng.gen_ratio(1, 30) {
+      lockstep.turn(directions[rng.gen_range(0..directions.len())]);
+    }
+    if !lockstep.tick(&mut world).expect("lost the connection") {
+      sleep(Duration::from_millis(1));
+    }
+  }
+
+  // Keep answering for a moment, so that the other peer can finish too
+  for _ in 0..500 {
+    let _ = lockstep.tick(&mut world);
+    sleep(Duration::from_millis(1));
+  }
+
+  match lockstep.desync {
+    Some(tick) => println!("out of sync at tick {}", tick),
+    None => println!("tick {} hash {:016x}", world.ticks, world.state_hash()),
+  }
+}
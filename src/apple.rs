@@ -1,53 +1,324 @@
-use crate::game::GameState;
-
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
+use crate::snek::{Direction, Snek};
+use crate::world::{GameState, TICKS_PER_SECOND};
 
+use rand::seq::SliceRandom;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+pub enum AppleKind {
+  /// Grows the snek by one
+  #[default]
+  Red,
+  /// Grows the snek by five, and is worth extra on top of that
+  Golden,
+  /// Shrinks the snek, and costs a point
+  Poison,
+  /// Grows the snek by one, and speeds it up for a while
+  Pepper,
+  /// Grows the snek by one, and slows it down for a while
+  Ice,
+}
+
+impl FromStr for AppleKind {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "red" => Ok(Self::Red),
+      "golden" => Ok(Self::Golden),
+      "poison" => Ok(Self::Poison),
+      "pepper" => Ok(Self::Pepper),
+      "ice" => Ok(Self::Ice),
+      _ => Err(format!(
+        "unknown apple `{}` (expected red, golden, poison, pepper or ice)",
+        s
+      )),
+    }
+  }
+}
+
+impl AppleKind {
+  /// How many cells eating this apple adds to (or, if negative, takes off of) a snek
+  pub fn growth(&self) -> i32 {
+    match self {
+      Self::Red | Self::Pepper | Self::Ice => 1,
+      Self::Golden => 5,
+      Self::Poison => -3,
+    }
+  }
+
+  /// How many of the mode's apple points this apple is worth
+  pub fn points(&self) -> i32 {
+    match self {
+      Self::Red | Self::Pepper | Self::Ice => 1,
+      Self::Golden => 10,
+      Self::Poison => -1,
+    }
+  }
+}
+
+/// How often each kind of apple grows, relative to the others
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct AppleTable {
+  pub red: u32,
+  pub golden: u32,
+  pub poison: u32,
+  pub pepper: u32,
+  pub ice: u32,
+}
+
+impl Default for AppleTable {
+  fn default() -> Self {
+    Self {
+      red: 80,
+      golden: 5,
+      poison: 7,
+      pepper: 4,
+      ice: 4,
+    }
+  }
+}
+
+/// Parses tables written like `red=80,golden=5`. Kinds that aren't listed never grow.
+impl FromStr for AppleTable {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut table = Self {
+      red: 0,
+      golden: 0,
+      poison: 0,
+      pepper: 0,
+      ice: 0,
+    };
+    for entry in s.split(',') {
+      let (kind, weight) = entry
+        .split_once('=')
+        .ok_or_else(|| format!("expected `<apple>=<weight>`, found `{}`", entry))?;
+      let weight = weight
+        .parse()
+        .map_err(|_| format!("`{}` isn't a valid weight", weight))?;
+      *table.weight_mut(kind.parse()?) = weight;
+    }
+    Ok(table)
+  }
+}
+
+impl AppleTable {
+  fn weight_mut(&mut self, kind: AppleKind) -> &mut u32 {
+    match kind {
+      AppleKind::Red => &mut self.red,
+      AppleKind::Golden => &mut self.golden,
+      AppleKind::Poison => &mut self.poison,
+      AppleKind::Pepper => &mut self.pepper,
+      AppleKind::Ice => &mut self.ice,
+    }
+  }
+
+  /// A random kind of apple, weighted by the table. Tables with no weight at all only grow red
+  /// apples.
+  pub fn pick(&self, rng: &mut impl Rng) -> AppleKind {
+    [
+      (AppleKind::Red, self.red),
+      (AppleKind::Golden, self.golden),
+      (AppleKind::Poison, self.poison),
+      (AppleKind::Pepper, self.pepper),
+      (AppleKind::Ice, self.ice),
+    ]
+    .choose_weighted(rng, |(_, weight)| *weight)
+    .map(|(kind, _)| *kind)
+    .unwrap_or_default()
+  }
+}
+
+/// Apples that are about to go off blink for this long first
+pub static BLINK_SECONDS: u32 = 2;
+
+/// Wandering apples take a step every this many game ticks
+pub static WANDER_TICKS: u64 = 30;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Apple {
   pub x: u32,
   pub y: u32,
+  #[serde(default)]
+  pub kind: AppleKind,
   pub eaten: bool,
+  /// Game ticks since the apple last grew
+  #[serde(default)]
+  pub age: u64,
 }
 
 impl Apple {
-  pub fn new(window_width: u32, window_height: u32, box_size: u32) -> Self {
-    let mut rng = rand::thread_rng();
+  pub fn new(x: u32, y: u32, kind: AppleKind) -> Self {
     Self {
-      x: rng.gen_range(0..window_width / box_size) * box_size,
-      y: rng.gen_range(0..window_height / box_size) * box_size,
+      x,
+      y,
+      kind,
       eaten: false,
+      age: 0,
     }
   }
 
-  pub fn tick(&mut self, game_state: &GameState) {
+  /// Game ticks until the apple goes off, if the rules or the game mode say apples go off. When
+  /// both do, it's whichever comes first.
+  pub fn ticks_left(&self, game_state: &GameState) -> Option<u64> {
+    let rules = &game_state.rules;
+    let lifetime = match (rules.apple_lifetime, rules.mode.spoil_seconds()) {
+      (Some(lifetime), Some(spoil)) => lifetime.min(spoil),
+      (lifetime, spoil) => lifetime.or(spoil)?,
+    };
+    Some((lifetime as u64 * TICKS_PER_SECOND as u64).saturating_sub(self.age))
+  }
+
+  /// Whether the apple should be drawn this tick. Apples that are about to go off blink.
+  pub fn visible(&self, game_state: &GameState) -> bool {
+    match self.ticks_left(game_state) {
+      Some(ticks_left) if ticks_left <= (BLINK_SECONDS * TICKS_PER_SECOND) as u64 => {
+        (ticks_left / (TICKS_PER_SECOND / 8) as u64).is_multiple_of(2)
+      }
+      _ => true,
+    }
+  }
+
+  /// Moves an apple that's gone off or been eaten somewhere that isn't in `occupied`, and marks its
+  /// new spot as occupied. It grows back as whatever kind the rules' apple table picks. Wandering
+  /// apples otherwise step away from the nearest of `heads`. Returns `false` if the apple needed to
+  /// move but there was nowhere left for it to go.
+  pub fn tick(
+    &mut self,
+    game_state: &GameState,
+    heads: &[(u32, u32)],
+    rng: &mut impl Rng,
+    occupied: &mut HashSet<(u32, u32)>,
+  ) -> bool {
+    self.age += 1;
+
+    if self.ticks_left(game_state) == Some(0) {
+      occupied.remove(&(self.x, self.y));
+      self.eaten = true;
+    }
+
     if self.eaten {
-      self.eaten = false;
-      let mut rng = rand::thread_rng();
+      match game_state.free_cell(occupied, rng) {
+        Some((x, y)) => {
+          self.eaten = false;
+          self.age = 0;
+          self.kind = game_state.rules.apples.pick(rng);
+          self.x = x;
+          self.y = y;
+          occupied.insert((x, y));
+        }
+        None => return false,
+      }
+    } else if game_state.rules.wandering_apples && self.age.is_multiple_of(WANDER_TICKS) {
+      self.wander(game_state, heads, occupied);
+    }
+
+    true
+  }
 
-      self.x =
-        rng.gen_range(0..game_state.window_width / game_state.box_size) * game_state.box_size;
+  /// Steps to whichever free neighbouring cell is furthest from the nearest head, if that's any
+  /// further than where the apple already is
+  fn wander(
+    &mut self,
+    game_state: &GameState,
+    heads: &[(u32, u32)],
+    occupied: &mut HashSet<(u32, u32)>,
+  ) {
+    let here = (self.x, self.y);
+    let Some(&head) = heads
+      .iter()
+      .min_by_key(|&&head| game_state.distance(head, here))
+    else {
+      return;
+    };
 
-      self.y =
-        rng.gen_range(0..game_state.window_height / game_state.box_size) * game_state.box_size;
+    let best = [
+      Direction::Up,
+      Direction::Down,
+      Direction::Left,
+      Direction::Right,
+    ]
+    .into_iter()
+    .filter_map(|direction| Snek::next_cell(game_state, direction, here))
+    .filter(|cell| {
+      !occupied.contains(cell)
+        && !game_state.walls.contains(cell)
+        && !game_state.portals.contains_key(cell)
+    })
+    .max_by_key(|&cell| game_state.distance(head, cell));
+
+    if let Some(cell) = best {
+      if game_state.distance(head, cell) > game_state.distance(head, here) {
+        occupied.remove(&here);
+        occupied.insert(cell);
+        (self.x, self.y) = cell;
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::world::Rules;
+
+  use rand::SeedableRng;
+  use rand_chacha::ChaCha12Rng;
+  use std::collections::HashMap;
+
+  fn board() -> GameState {
+    GameState {
+      cols: 8,
+      rows: 6,
+      rules: Rules::default(),
+      walls: HashSet::new(),
+      portals: HashMap::new(),
     }
   }
 
-  pub fn draw(&self, game_state: &GameState, canvas: &mut Canvas<Window>) {
-    // Draw apple
-    canvas.set_draw_color(Color::RED);
-    canvas
-      .fill_rect(Rect::new(
-        self.x as i32,
-        self.y as i32,
-        game_state.box_size,
-        game_state.box_size,
-      ))
-      .unwrap();
+  #[test]
+  fn parses_apple_tables() {
+    let table: AppleTable = "golden=3,ice=1".parse().unwrap();
+    assert_eq!(
+      (table.red, table.golden, table.poison, table.pepper, table.ice),
+      (0, 3, 0, 0, 1)
+    );
+
+    assert!("red".parse::<AppleTable>().is_err());
+    assert!("red=lots".parse::<AppleTable>().is_err());
+    assert!("blue=1".parse::<AppleTable>().is_err());
+  }
+
+  #[test]
+  fn picks_apples_by_weight() {
+    let mut rng = ChaCha12Rng::seed_from_u64(1);
+    let table: AppleTable = "red=3,poison=1".parse().unwrap();
+    let picks: Vec<AppleKind> = (0..4000).map(|_| table.pick(&mut rng)).collect();
+    let poison = picks.iter().filter(|&&kind| kind == AppleKind::Poison).count();
+    assert!(picks.iter().all(|&kind| kind == AppleKind::Red || kind == AppleKind::Poison));
+    assert!((900..1100).contains(&poison), "{} poison apples", poison);
+
+    let nothing: AppleTable = "golden=0".parse().unwrap();
+    assert_eq!(nothing.pick(&mut rng), AppleKind::Red);
+  }
+
+  #[test]
+  fn wanders_away_without_landing_on_a_portal() {
+    let mut game_state = board();
+    game_state.portals.insert((0, 1), (6, 4));
+    game_state.portals.insert((6, 4), (0, 1));
+
+    let mut apple = Apple::new(1, 1, AppleKind::Red);
+    let mut occupied = HashSet::from([(1, 1)]);
+    apple.wander(&game_state, &[(3, 1)], &mut occupied);
+
+    let cell = (apple.x, apple.y);
+    assert!(cell == (1, 0) || cell == (1, 2), "wandered to {:?}", cell);
+    assert_eq!(occupied, HashSet::from([cell]));
   }
 }
@@ -0,0 +1,96 @@
+//! Runs rounds with no window at all, for clients to connect to with `snek --connect` (or to watch
+//! with `snek --watch`)
+
+use snek::level::Level;
+use snek::mode::GameMode;
+use snek::server::Server;
+use snek::world::{random_seed, CollisionRule, EdgeMode, Rules, World, TICKS_PER_SECOND};
+
+use std::path::PathBuf;
+use std::process::exit;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+static USAGE: &str = "usage: snek-server [--port <port>] [--players <number>] [--level <file>]
+                   [--seed <number>] [--mode classic|time-attack|survival]
+                   [--collisions pass-through|both-die|longer-wins] [--edges wrap|walls|bounce]";
+
+/// Boards without a level are this many cells across, the same as a default window's
+static DEFAULT_COLS: u32 = 80;
+static DEFAULT_ROWS: u32 = 53;
+
+fn main() {
+  let mut port: u16 = 7878;
+  let mut players: usize = 2;
+  let mut level: Option<PathBuf> = None;
+  let mut seed = random_seed();
+  let mut rules = Rules::default();
+
+  let mut args = std::env::args().skip(1);
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--port" => port = parse(&arg, args.next()),
+      "--players" => players = parse(&arg, args.next()),
+      "--level" => level = Some(parse(&arg, args.next())),
+      "--seed" => seed = parse(&arg, args.next()),
+      "--mode" => rules.mode = parse::<GameMode>(&arg, args.next()),
+      "--collisions" => rules.collisions = parse::<CollisionRule>(&arg, args.next()),
+      "--edges" => rules.edges = parse::<EdgeMode>(&arg, args.next()),
+      "-h" | "--help" => {
+        println!("{}", USAGE);
+        exit(0);
+      }
+      _ => fail(&format!("unknown argument `{}`", arg)),
+    }
+  }
+  if players == 0 {
+    fail("--players: there has to be at least one");
+  }
+
+  let level = match level {
+    Some(path) => Level::load(&path).unwrap_or_else(|e| fail(&e)),
+    None => Level::empty(DEFAULT_COLS, DEFAULT_ROWS),
+  };
+  let world = World::new(level, rules, players, seed);
+  let mut server = Server::bind(("0.0.0.0", port), world)
+    .unwrap_or_else(|e| fail(&format!("couldn't listen on port {}: {}", port, e)));
+  println!(
+    "Serving a round for {} players on port {} (seed {})",
+    players, port, seed
+  );
+
+  // The same fixed timestep as the game itself, minus the drawing
+  let tick_length = Duration::from_secs(1) / TICKS_PER_SECOND;
+  let mut next_tick = Instant::now();
+  loop {
+    if let Err(e) = server.tick() {
+      fail(&format!("the server stopped: {}", e));
+    }
+
+    next_tick += tick_length;
+    let now = Instant::now();
+    if next_tick > now {
+      sleep(next_tick - now);
+    } else {
+      // Don't try to catch up after falling far behind
+      next_tick = now;
+    }
+  }
+}
+
+fn parse<T: std::str::FromStr>(arg: &str, value: Option<String>) -> T
+where
+  T::Err: std::fmt::Display,
+{
+  match value {
+    Some(value) => value
+      .parse()
+      .unwrap_or_else(|e| fail(&format!("{}: {}", arg, e))),
+    None => fail(&format!("{} needs a value", arg)),
+  }
+}
+
+fn fail(message: &str) -> ! {
+  eprintln!("{}\n{}", message, USAGE);
+  exit(1);
+}
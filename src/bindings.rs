@@ -0,0 +1,197 @@
+use snek::file::{load_config, save_config};
+use snek::snek::Direction;
+
+use sdl2::keyboard::Keycode;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Bindings are kept in this file in the config directory
+static BINDINGS_FILE: &str = "bindings.json";
+
+/// Opens the rebinding screen. This and Escape can't be rebound, so there's always a way back.
+pub static REBIND_KEY: Keycode = Keycode::F1;
+
+/// Everything a player can ask of the game, whatever they asked with
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Action {
+  Up,
+  Down,
+  Left,
+  Right,
+  /// Pauses or unpauses, or starts a new round once this one's over
+  Pause,
+  /// Starts a new round once this one's over
+  Restart,
+}
+
+impl Action {
+  pub fn direction(&self) -> Option<Direction> {
+    match self {
+      Self::Up => Some(Direction::Up),
+      Self::Down => Some(Direction::Down),
+      Self::Left => Some(Direction::Left),
+      Self::Right => Some(Direction::Right),
+      Self::Pause | Self::Restart => None,
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Up => "Up",
+      Self::Down => "Down",
+      Self::Left => "Left",
+      Self::Right => "Right",
+      Self::Pause => "Pause",
+      Self::Restart => "Restart",
+    }
+  }
+}
+
+/// A key, and what it does for which player
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Binding {
+  #[serde(serialize_with = "serialize_key", deserialize_with = "deserialize_key")]
+  pub key: Keycode,
+  pub player: usize,
+  pub action: Action,
+}
+
+/// Which keys do what
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Bindings(pub Vec<Binding>);
+
+impl Default for Bindings {
+  /// WASD, the arrows, IJKL and the numpad steer players one to four; Space pauses and Enter
+  /// restarts
+  fn default() -> Self {
+    let steering = [
+      [Keycode::W, Keycode::S, Keycode::A, Keycode::D],
+      [Keycode::Up, Keycode::Down, Keycode::Left, Keycode::Right],
+      [Keycode::I, Keycode::K, Keycode::J, Keycode::L],
+      [Keycode::Kp8, Keycode::Kp5, Keycode::Kp4, Keycode::Kp6],
+    ];
+    let directions = [Action::Up, Action::Down, Action::Left, Action::Right];
+
+    let mut bindings: Vec<Binding> = steering
+      .iter()
+      .enumerate()
+      .flat_map(|(player, keys)| {
+        keys.iter().zip(directions).map(move |(&key, action)| Binding {
+          key,
+          player,
+          action,
+        })
+      })
+      .collect();
+    bindings.push(Binding {
+      key: Keycode::Space,
+      player: 0,
+      action: Action::Pause,
+    });
+    bindings.push(Binding {
+      key: Keycode::Return,
+      player: 0,
+      action: Action::Restart,
+    });
+
+    Self(bindings)
+  }
+}
+
+impl Bindings {
+  /// The saved bindings, or the defaults if there aren't any (or they can't be read)
+  pub fn load() -> Self {
+    load_config(BINDINGS_FILE)
+      .and_then(|json| serde_json::from_str(&json).ok())
+      .unwrap_or_default()
+  }
+
+  pub fn save(&self) {
+    if let Ok(json) = serde_json::to_string_pretty(self) {
+      save_config(BINDINGS_FILE, &json);
+    }
+  }
+
+  /// Everything `key` does, and for which player
+  pub fn actions(&self, key: Keycode) -> impl Iterator<Item = (usize, Action)> + '_ {
+    self
+      .0
+      .iter()
+      .filter(move |binding| binding.key == key)
+      .map(|binding| (binding.player, binding.action))
+  }
+
+  /// The names of the keys bound to `action` for `player`
+  pub fn keys(&self, player: usize, action: Action) -> Vec<String> {
+    self
+      .0
+      .iter()
+      .filter(|binding| binding.player == player && binding.action == action)
+      .map(|binding| binding.key.name())
+      .collect()
+  }
+
+  /// Makes `key` the only key for `action` for `player`, and takes it off of whatever else it did
+  pub fn bind(&mut self, key: Keycode, player: usize, action: Action) {
+    self.0.retain(|binding| {
+      binding.key != key && !(binding.player == player && binding.action == action)
+    });
+    self.0.push(Binding {
+      key,
+      player,
+      action,
+    });
+  }
+}
+
+/// Steps through every action, one at a time, asking for a new key for each of them
+pub struct Rebinding {
+  /// The bindings as they'll be once the screen is done, which only replace the real ones then
+  pub draft: Bindings,
+  /// Every action that gets asked about, and for which player
+  pub steps: Vec<(usize, Action)>,
+  pub step: usize,
+}
+
+impl Rebinding {
+  /// Asks about steering for each of `players`, and then pausing and restarting
+  pub fn new(bindings: &Bindings, players: usize) -> Self {
+    let mut steps: Vec<(usize, Action)> = (0..players)
+      .flat_map(|player| {
+        [Action::Up, Action::Down, Action::Left, Action::Right]
+          .into_iter()
+          .map(move |action| (player, action))
+      })
+      .collect();
+    steps.push((0, Action::Pause));
+    steps.push((0, Action::Restart));
+
+    Self {
+      draft: bindings.clone(),
+      steps,
+      step: 0,
+    }
+  }
+
+  /// Binds `key` to the action being asked about, or skips it for Backspace, and moves on to the
+  /// next one. Returns `true` once every action has been asked about.
+  pub fn press(&mut self, key: Keycode) -> bool {
+    if key != Keycode::Backspace {
+      let (player, action) = self.steps[self.step];
+      self.draft.bind(key, player, action);
+    }
+    self.step += 1;
+    self.step == self.steps.len()
+  }
+}
+
+// Keys are saved by name, so that the bindings file can be edited by hand
+
+fn serialize_key<S: Serializer>(key: &Keycode, serializer: S) -> Result<S::Ok, S::Error> {
+  serializer.serialize_str(&key.name())
+}
+
+fn deserialize_key<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Keycode, D::Error> {
+  let name = String::deserialize(deserializer)?;
+  Keycode::from_name(&name)
+    .ok_or_else(|| serde::de::Error::custom(format!("unknown key `{}`", name)))
+}
@@ -0,0 +1,790 @@
+//! Sneks that steer themselves. Bots only think when their snek is about to move: they look at
+//! the board as it is, work out where to go, and turn that way.
+
+use crate::apple::AppleKind;
+use crate::controller::SnekController;
+use crate::snek::{Direction, Snek, START_LEN};
+use crate::world::{CollisionRule, GameState, Status, World};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::str::FromStr;
+
+type Cell = (u32, u32);
+
+static DIRECTIONS: [Direction; 4] = [
+  Direction::Up,
+  Direction::Down,
+  Direction::Left,
+  Direction::Right,
+];
+
+/// How a bot decides where to go
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum Strategy {
+  /// Takes the shortest way to the nearest apple, and hopes for the best once it's there
+  Greedy,
+  /// Only goes for apples it can get back out from after eating, and otherwise plays for time
+  #[default]
+  Safe,
+  /// Goes round and round a path through every cell of the board, which it can never get stuck on
+  /// once its body's lined up along it, for as long as it fits. Sneks line up from the start of a
+  /// round, but a long, tangled snek can take a while. Boards with walls or portals only have a
+  /// path through their open 2×2 blocks of cells, so apples off the path are fetched the safe way.
+  Hamiltonian,
+}
+
+impl FromStr for Strategy {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "greedy" => Ok(Self::Greedy),
+      "safe" => Ok(Self::Safe),
+      "hamiltonian" => Ok(Self::Hamiltonian),
+      _ => Err(format!(
+        "unknown strategy `{}` (expected greedy, safe or hamiltonian)",
+        s
+      )),
+    }
+  }
+}
+
+/// How well a bot plays
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum Difficulty {
+  Easy,
+  #[default]
+  Normal,
+  Hard,
+}
+
+impl Difficulty {
+  /// How often the bot doesn't look where it's going, and just carries on the way it was headed
+  fn blunder_chance(&self) -> f64 {
+    match self {
+      Self::Easy => 0.15,
+      Self::Normal => 0.03,
+      Self::Hard => 0.0,
+    }
+  }
+}
+
+impl FromStr for Difficulty {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "easy" => Ok(Self::Easy),
+      "normal" => Ok(Self::Normal),
+      "hard" => Ok(Self::Hard),
+      _ => Err(format!(
+        "unknown difficulty `{}` (expected easy, normal or hard)",
+        s
+      )),
+    }
+  }
+}
+
+/// What kind of bot steers a snek, kept with the snek so that it's still a bot after a restart or
+/// a reload
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct BotSettings {
+  pub strategy: Strategy,
+  pub difficulty: Difficulty,
+}
+
+/// Steers a snek by itself, however its settings say to
+pub struct Bot {
+  pub settings: BotSettings,
+  /// Only used for blunders, so that bots don't touch the world's own RNG
+  rng: ChaCha12Rng,
+  /// The tick the bot last decided on, so that it doesn't decide twice while the world's paused
+  decided_at: Option<u64>,
+  /// The Hamiltonian cycle for the board the bot last saw, if it has one
+  cycle: Option<Cycle>,
+}
+
+impl Bot {
+  pub fn new(settings: BotSettings, seed: u64) -> Self {
+    Self {
+      settings,
+      rng: ChaCha12Rng::seed_from_u64(seed),
+      decided_at: None,
+      cycle: None,
+    }
+  }
+
+  fn decide(&mut self, world: &World, snek: usize) -> Option<Direction> {
+    let this = &world.sneks[snek];
+    let board = Board::new(world, snek, this.positions(), growth(this), Some(this.heading()));
+    let plan = match self.settings.strategy {
+      Strategy::Greedy => board.to_nearest_apple(world).or_else(|| board.roomiest()),
+      Strategy::Safe => safe(world, snek, &board),
+      Strategy::Hamiltonian => self
+        .along_cycle(world, snek, &board)
+        .or_else(|| safe(world, snek, &board)),
+    };
+    plan.or_else(|| board.risky())
+  }
+
+  /// The way along the board's Hamiltonian cycle, unless the only apples are off it. A snek that's
+  /// off the cycle, or whose body isn't lined up along it yet, steps onto whichever cell it could
+  /// follow the cycle round from without running into anything, preferring the next one along.
+  /// Failing that, it heads for the nearest such cell further off, or plays for time until its
+  /// body's out of the way.
+  fn along_cycle(&mut self, world: &World, snek: usize, board: &Board) -> Option<Direction> {
+    let game_state = &world.game_state;
+    if self
+      .cycle
+      .as_ref()
+      .is_none_or(|cycle| (cycle.cols, cycle.rows) != (game_state.cols, game_state.rows))
+    {
+      self.cycle = Cycle::new(game_state);
+    }
+    let cycle = self.cycle.as_ref()?;
+
+    let mut apples = edible_apples(world).peekable();
+    if apples.peek().is_some() && !apples.any(|apple| cycle.order.contains_key(&apple)) {
+      return None;
+    }
+
+    let this = &world.sneks[snek];
+    let next = cycle.after(board.head);
+    let mut moves: Vec<(Direction, Cell)> = board.moves(board.head).collect();
+    moves.sort_by_key(|&(_, cell)| Some(cell) != next);
+    moves
+      .into_iter()
+      .find(|&(_, cell)| {
+        // An apple on the way makes the tail wait, which has to be allowed for before eating it
+        let (positions, growth) = step_into(this, cell);
+        let after = Board::new(world, snek, &positions, growth + apple_growth(world, cell), None);
+        cycle.slack(&after, cell).is_some_and(|slack| slack >= 0)
+      })
+      .map(|(direction, _)| direction)
+      .or_else(|| towards_cycle(cycle, world, snek, board))
+      .or_else(|| stall(world, snek, board))
+  }
+}
+
+impl SnekController for Bot {
+  fn next_direction(&mut self, world: &World, snek: usize) -> Option<Direction> {
+    let this = &world.sneks[snek];
+    if world.status != Status::Playing
+      || !this.alive
+      || this.ticks_until_step() != 1
+      || self.decided_at == Some(world.ticks)
+    {
+      return None;
+    }
+    self.decided_at = Some(world.ticks);
+
+    if self.rng.gen_bool(self.settings.difficulty.blunder_chance()) {
+      return None;
+    }
+    self.decide(world, snek)
+  }
+}
+
+/// Heads for the nearest apple that it can still follow its tail from after eating, and otherwise
+/// stalls to wait for a better chance
+fn safe(world: &World, snek: usize, board: &Board) -> Option<Direction> {
+  let this = &world.sneks[snek];
+  let paths = board.paths();
+
+  let mut apples: Vec<(u32, Cell)> = edible_apples(world)
+    .filter_map(|apple| Some((paths.steps(apple)?, apple)))
+    .collect();
+  apples.sort();
+  for (_, apple) in apples {
+    // Move a copy of the snek along the path, and see whether it could reach its tail from there
+    let mut positions = this.positions().to_vec();
+    positions.extend(paths.path_to(apple));
+    let kind = world.apples.iter().find(|a| (a.x, a.y) == apple).map(|a| a.kind);
+    let len = this
+      .len
+      .saturating_add_signed(kind.map_or(0, |kind| kind.growth()))
+      .max(START_LEN);
+    let excess = positions.len().saturating_sub(len as usize);
+    positions.drain(..excess);
+
+    let growth = len.saturating_sub(positions.len() as u32);
+    let after = Board::new(world, snek, &positions, growth, None);
+    if after.paths().steps(positions[0]).is_some() {
+      return paths.first(apple);
+    }
+  }
+
+  stall(world, snek, board).or_else(|| board.roomiest())
+}
+
+/// The first step of the shortest way to a cell that the snek could follow the cycle from, with
+/// its body as it would be by the time it got there, for when no single step will do. Only the
+/// snek's own body is allowed for, since other sneks will have gone who knows where by then.
+fn towards_cycle(cycle: &Cycle, world: &World, snek: usize, board: &Board) -> Option<Direction> {
+  let this = &world.sneks[snek];
+  let paths = board.paths();
+  let mut targets: Vec<(u32, Cell)> = cycle
+    .cells
+    .iter()
+    .filter_map(|&cell| Some((paths.steps(cell)?, cell)))
+    .collect();
+  targets.sort();
+  targets.into_iter().find_map(|(_, cell)| {
+    let path = paths.path_to(cell);
+    let eaten: u32 = path.iter().map(|&cell| apple_growth(world, cell)).sum();
+    let mut positions = this.positions().to_vec();
+    positions.extend(path);
+    let excess = positions.len().saturating_sub(this.len as usize);
+    positions.drain(..excess);
+    let growth = (this.len + eaten).saturating_sub(positions.len() as u32);
+    (cycle.body_slack(&positions, growth) >= 0).then(|| paths.first(cell))?
+  })
+}
+
+/// How much a snek grows by from eating whatever apple's on `cell`. Poison counts for nothing,
+/// since shrinking never gets anyone into trouble.
+fn apple_growth(world: &World, cell: Cell) -> u32 {
+  world
+    .apples
+    .iter()
+    .filter(|apple| !apple.eaten && (apple.x, apple.y) == cell)
+    .map(|apple| apple.kind.growth().max(0) as u32)
+    .sum()
+}
+
+/// Where a snek's body would be after stepping into `cell` (tail first), and how much it would
+/// still have to grow into
+fn step_into(snek: &Snek, cell: Cell) -> (Vec<Cell>, u32) {
+  let mut positions = snek.positions().to_vec();
+  positions.push(cell);
+  let excess = positions.len().saturating_sub(snek.len as usize);
+  positions.drain(..excess);
+  let growth = snek.len.saturating_sub(positions.len() as u32);
+  (positions, growth)
+}
+
+/// Plays for time: of the ways the snek can still follow its tail from, takes the one that puts
+/// it farthest behind its tail, so that it uses its room up slowly rather than curling up tight
+fn stall(world: &World, snek: usize, board: &Board) -> Option<Direction> {
+  let this = &world.sneks[snek];
+  board
+    .moves(board.head)
+    .filter_map(|(direction, cell)| {
+      let (positions, growth) = step_into(this, cell);
+      let after = Board::new(world, snek, &positions, growth, None);
+      let steps = after.paths().steps(positions[0])?;
+      Some((steps, direction))
+    })
+    .max_by_key(|&(steps, _)| steps)
+    .map(|(_, direction)| direction)
+}
+
+/// Every apple worth eating. Poison is only worth it when there's nothing else, since otherwise
+/// the round would never go anywhere.
+fn edible_apples(world: &World) -> impl Iterator<Item = Cell> + '_ {
+  let uneaten = || world.apples.iter().filter(|apple| !apple.eaten);
+  let only_poison = uneaten().all(|apple| apple.kind == AppleKind::Poison);
+  uneaten()
+    .filter(move |apple| only_poison || apple.kind != AppleKind::Poison)
+    .map(|apple| (apple.x, apple.y))
+}
+
+/// How many more cells the snek still has to grow into
+fn growth(snek: &Snek) -> u32 {
+  snek.len.saturating_sub(snek.positions().len() as u32)
+}
+
+/// The board as one snek sees it: for every cell that something's in the way in, how many of the
+/// snek's own steps it'll take to clear
+struct Board<'w> {
+  game_state: &'w GameState,
+  head: Cell,
+  /// Which way the snek's going, which it can't turn straight back on. Imagined sneks aren't
+  /// going anywhere yet, and their necks are in the way of turning back anyway.
+  heading: Option<Direction>,
+  clear_after: Grid<u32>,
+  /// Cells that another snek's head could move into next, which are best kept out of when
+  /// there's anywhere else to go
+  contested: HashSet<Cell>,
+}
+
+impl<'w> Board<'w> {
+  /// The board for snek number `snek` if its body were `positions` (tail first), with `growth`
+  /// cells still to grow into
+  fn new(
+    world: &'w World,
+    snek: usize,
+    positions: &[Cell],
+    growth: u32,
+    heading: Option<Direction>,
+  ) -> Self {
+    let game_state = &world.game_state;
+    let mut clear_after = Grid::new(game_state, 0);
+    let mut block = |cell: Cell, steps: u32| {
+      let after = clear_after.get_mut(cell);
+      *after = (*after).max(steps);
+    };
+
+    for &wall in &game_state.walls {
+      block(wall, u32::MAX);
+    }
+
+    // Each segment moves on once the segments behind it have, plus however much is left to grow
+    for (i, &cell) in positions.iter().enumerate() {
+      block(cell, i as u32 + 1 + growth);
+    }
+
+    // Other sneks move at their own pace, so each of their segments clears after however many of
+    // this snek's steps it takes to get to the tick it moves on
+    let this = &world.sneks[snek];
+    let steps_until = |ticks: u32| {
+      let ticks = ticks.saturating_sub(this.ticks_until_step());
+      ticks.div_ceil(this.step_ticks().max(1)) + 1
+    };
+
+    let mut contested = HashSet::new();
+    if game_state.rules.collisions != CollisionRule::PassThrough {
+      for (i, other) in world.sneks.iter().enumerate() {
+        if i == snek {
+          continue;
+        }
+        if !other.alive {
+          for &cell in other.positions() {
+            block(cell, u32::MAX);
+          }
+          continue;
+        }
+
+        for (j, &cell) in other.positions().iter().enumerate() {
+          let steps = j as u32 + 1 + self::growth(other);
+          block(cell, steps_until(other.ticks_until_step() + (steps - 1) * other.step_ticks()));
+        }
+
+        // Whoever gives way keeps clear of everywhere the other snek could go next, and whoever
+        // doesn't still keeps out of its way. Bots give way to players, and to the bots before
+        // them, so that two bots never wait on each other forever. Nobody needs to give way to a
+        // snek that would lose the head-on.
+        let outmatched = match game_state.rules.collisions {
+          CollisionRule::LongerWins => other.len >= this.len,
+          _ => true,
+        };
+        let gives_way = other.bot.is_none() || i < snek;
+        for direction in DIRECTIONS {
+          if outmatched && (gives_way || direction == other.heading()) {
+            if let Some(cell) = Snek::next_cell(game_state, direction, other.position()) {
+              contested.insert(cell);
+            }
+          }
+        }
+      }
+    }
+
+    Self {
+      game_state,
+      head: *positions.last().unwrap(),
+      heading,
+      clear_after,
+      contested,
+    }
+  }
+
+  fn clear(&self, cell: Cell, steps: u32) -> bool {
+    steps >= self.clear_after.get(cell)
+  }
+
+  /// Each way the snek can go from `head` on its next step without running into anything
+  fn moves(&self, head: Cell) -> impl Iterator<Item = (Direction, Cell)> + '_ {
+    DIRECTIONS
+      .into_iter()
+      .filter(move |&direction| Some(direction.opposite()) != self.heading)
+      .filter_map(move |direction| {
+        let cell = Snek::next_cell(self.game_state, direction, head)?;
+        (self.clear(cell, 1) && !self.contested.contains(&cell)).then_some((direction, cell))
+      })
+  }
+
+  /// Shortest paths from the head to everywhere it can get to
+  fn paths(&self) -> Paths {
+    let mut paths = Paths {
+      reached: Grid::new(self.game_state, None),
+    };
+    let mut queue = VecDeque::new();
+    for (direction, cell) in self.moves(self.head) {
+      if paths.reached.get(cell).is_none() {
+        *paths.reached.get_mut(cell) = Some((1, direction, None));
+        queue.push_back(cell);
+      }
+    }
+
+    while let Some(cell) = queue.pop_front() {
+      let (steps, first, _) = paths.reached.get(cell).unwrap();
+      for direction in DIRECTIONS {
+        let Some(next) = Snek::next_cell(self.game_state, direction, cell) else {
+          continue;
+        };
+        let reached = paths.reached.get_mut(next);
+        if next == self.head || reached.is_some() || !self.clear(next, steps + 1) {
+          continue;
+        }
+        *reached = Some((steps + 1, first, Some(cell)));
+        queue.push_back(next);
+      }
+    }
+    paths
+  }
+
+  fn to_nearest_apple(&self, world: &World) -> Option<Direction> {
+    let paths = self.paths();
+    let nearest = edible_apples(world).min_by_key(|&apple| paths.steps(apple).unwrap_or(u32::MAX))?;
+    paths.first(nearest)
+  }
+
+  /// A way that's only blocked if another snek gets there first, for when that's the only hope
+  fn risky(&self) -> Option<Direction> {
+    DIRECTIONS
+      .into_iter()
+      .filter(|&direction| Some(direction.opposite()) != self.heading)
+      .filter(|&direction| {
+        Snek::next_cell(self.game_state, direction, self.head).is_some_and(|cell| self.clear(cell, 1))
+      })
+      .max_by_key(|&direction| Some(direction) == self.heading)
+  }
+
+  /// The way with the most room to move around in, for when there's no better plan. Keeps going
+  /// straight when nothing beats it.
+  fn roomiest(&self) -> Option<Direction> {
+    let paths = self.paths();
+    let mut room = [0; 4];
+    for &(_, first, _) in paths.reached.cells.iter().flatten() {
+      room[first as usize] += 1;
+    }
+    self
+      .moves(self.head)
+      .map(|(direction, _)| (direction, room[direction as usize]))
+      .max_by_key(|&(direction, room)| (room, Some(direction) == self.heading))
+      .map(|(direction, _)| direction)
+  }
+}
+
+/// The result of a search outwards from a snek's head
+struct Paths {
+  /// For each cell the snek can get to: how many steps it takes, which way it has to go first,
+  /// and which cell it gets there from (unless it's right next to the head)
+  reached: Grid<Option<(u32, Direction, Option<Cell>)>>,
+}
+
+impl Paths {
+  fn steps(&self, cell: Cell) -> Option<u32> {
+    self.reached.get(cell).map(|(steps, _, _)| steps)
+  }
+
+  fn first(&self, cell: Cell) -> Option<Direction> {
+    self.reached.get(cell).map(|(_, first, _)| first)
+  }
+
+  /// Every cell on the way to `cell`, ending with it
+  fn path_to(&self, cell: Cell) -> Vec<Cell> {
+    let mut path = vec![cell];
+    while let Some((_, _, Some(parent))) = self.reached.get(*path.last().unwrap()) {
+      path.push(parent);
+    }
+    path.reverse();
+    path
+  }
+}
+
+/// One of something for every cell on the board
+struct Grid<T> {
+  cols: u32,
+  cells: Vec<T>,
+}
+
+impl<T: Copy> Grid<T> {
+  fn new(game_state: &GameState, value: T) -> Self {
+    Self {
+      cols: game_state.cols,
+      cells: vec![value; (game_state.cols * game_state.rows) as usize],
+    }
+  }
+
+  fn get(&self, (x, y): Cell) -> T {
+    self.cells[(y * self.cols + x) as usize]
+  }
+
+  fn get_mut(&mut self, (x, y): Cell) -> &mut T {
+    &mut self.cells[(y * self.cols + x) as usize]
+  }
+}
+
+/// A path through every cell of the board (or as many as it can manage) that comes back around to
+/// where it started
+struct Cycle {
+  cols: u32,
+  rows: u32,
+  /// Where each cell comes along the cycle
+  order: HashMap<Cell, usize>,
+  cells: Vec<Cell>,
+}
+
+impl Cycle {
+  /// On an empty board with an even number of columns (or of rows, turned on its side), runs up and
+  /// down the columns below the top row, then back along the top row, through every cell. Other
+  /// boards get a cycle around the largest connected group of 2×2 blocks that are clear of walls
+  /// and portals, or none if there isn't a single one.
+  fn new(game_state: &GameState) -> Option<Self> {
+    let (cols, rows) = (game_state.cols, game_state.rows);
+    let empty = game_state.walls.is_empty() && game_state.portals.is_empty();
+    let cells: Vec<Cell> = if empty && cols.is_multiple_of(2) && rows >= 2 {
+      snake_columns(cols, rows)
+    } else if empty && rows.is_multiple_of(2) && cols >= 2 {
+      snake_columns(rows, cols)
+        .into_iter()
+        .map(|(y, x)| (x, y))
+        .collect()
+    } else {
+      // Blocks can start on odd cells as well as even ones, which suits boards walled all around
+      [(0, 0), (1, 0), (0, 1), (1, 1)]
+        .into_iter()
+        .filter_map(|offset| around_blocks(game_state, offset))
+        .max_by_key(Vec::len)?
+    };
+
+    Some(Self {
+      cols,
+      rows,
+      order: cells.iter().enumerate().map(|(i, &cell)| (cell, i)).collect(),
+      cells,
+    })
+  }
+
+  /// The cell after `cell` along the cycle, if `cell` is on it
+  fn after(&self, cell: Cell) -> Option<Cell> {
+    Some(self.cells[(self.order.get(&cell)? + 1) % self.cells.len()])
+  }
+
+  /// How many steps to spare a snek with its head on `head` would have following the cycle from
+  /// there, at the cell where it has the least. If that's never negative, every cell it comes to
+  /// is clear by the time it gets there, and once it's been once around, its body is all lined up
+  /// behind it with everything ahead clear; so it can follow the cycle forever. `None` when `head`
+  /// isn't on the cycle at all.
+  fn slack(&self, board: &Board, head: Cell) -> Option<i64> {
+    let start = self.order.get(&head)?;
+    (1..=self.cells.len())
+      .map(|steps| {
+        let cell = self.cells[(start + steps) % self.cells.len()];
+        steps as i64 - board.clear_after.get(cell) as i64
+      })
+      .min()
+  }
+
+  /// Like `slack`, but for a snek with its body on `positions` (tail first, ending with its head on
+  /// the cycle) and `growth` still to grow into, ignoring everything else on the board
+  fn body_slack(&self, positions: &[Cell], growth: u32) -> i64 {
+    let n = self.cells.len();
+    let head = self.order[positions.last().unwrap()];
+    positions
+      .iter()
+      .enumerate()
+      .filter_map(|(i, cell)| {
+        let steps = (self.order.get(cell)? + n - head - 1) % n + 1;
+        Some(steps as i64 - (i as u32 + 1 + growth) as i64)
+      })
+      .min()
+      .unwrap_or(0)
+  }
+}
+
+fn snake_columns(cols: u32, rows: u32) -> Vec<Cell> {
+  let mut cells = Vec::new();
+  for x in 0..cols {
+    if x % 2 == 0 {
+      cells.extend((1..rows).map(|y| (x, y)));
+    } else {
+      cells.extend((1..rows).rev().map(|y| (x, y)));
+    }
+  }
+  cells.extend((0..cols).rev().map(|x| (x, 0)));
+  cells
+}
+
+/// Splits the board into 2×2 blocks, starting `offset` cells in from the top left, joins up the
+/// largest connected group of blocks that are clear of walls and portals with a spanning tree, and
+/// walks clockwise around the outside of the tree. Inside each block the walk goes clockwise around
+/// the block, except where it crosses over into a neighbour the tree joins it to.
+fn around_blocks(game_state: &GameState, (ox, oy): Cell) -> Option<Vec<Cell>> {
+  let cols = game_state.cols.saturating_sub(ox) / 2;
+  let rows = game_state.rows.saturating_sub(oy) / 2;
+  let open = |(bx, by): Cell| {
+    [(0, 0), (1, 0), (0, 1), (1, 1)].iter().all(|&(dx, dy)| {
+      let cell = (ox + 2 * bx + dx, oy + 2 * by + dy);
+      !game_state.walls.contains(&cell) && !game_state.portals.contains_key(&cell)
+    })
+  };
+  let neighbours = |(bx, by): Cell| {
+    [
+      (bx.checked_sub(1), Some(by)),
+      (Some(bx + 1).filter(|&bx| bx < cols), Some(by)),
+      (Some(bx), by.checked_sub(1)),
+      (Some(bx), Some(by + 1).filter(|&by| by < rows)),
+    ]
+    .into_iter()
+    .filter_map(|(bx, by)| Some((bx?, by?)))
+  };
+
+  // Each block found from the first block of its group, along with the block it was found from
+  let mut found: HashMap<Cell, Option<Cell>> = HashMap::new();
+  let mut largest: Vec<Cell> = Vec::new();
+  for block in (0..rows).flat_map(|by| (0..cols).map(move |bx| (bx, by))) {
+    if found.contains_key(&block) || !open(block) {
+      continue;
+    }
+    found.insert(block, None);
+    let mut group = vec![block];
+    let mut queue = VecDeque::from([block]);
+    while let Some(block) = queue.pop_front() {
+      for next in neighbours(block) {
+        if open(next) && !found.contains_key(&next) {
+          found.insert(next, Some(block));
+          group.push(next);
+          queue.push_back(next);
+        }
+      }
+    }
+    if group.len() > largest.len() {
+      largest = group;
+    }
+  }
+  let &first = largest.first()?;
+
+  let joined = |a: Cell, b: Cell| found[&a] == Some(b) || found.get(&b) == Some(&Some(a));
+  let after = |(x, y): Cell| {
+    let block = ((x - ox) / 2, (y - oy) / 2);
+    match ((x - ox) % 2, (y - oy) % 2) {
+      (0, 0) if block.1 > 0 && joined(block, (block.0, block.1 - 1)) => (x, y - 1),
+      (0, 0) => (x + 1, y),
+      (1, 0) if joined(block, (block.0 + 1, block.1)) => (x + 1, y),
+      (1, 0) => (x, y + 1),
+      (1, 1) if joined(block, (block.0, block.1 + 1)) => (x, y + 1),
+      (1, 1) => (x - 1, y),
+      _ if block.0 > 0 && joined(block, (block.0 - 1, block.1)) => (x - 1, y),
+      _ => (x, y - 1),
+    }
+  };
+
+  let start = (ox + 2 * first.0, oy + 2 * first.1);
+  let mut cells = vec![start];
+  let mut cell = after(start);
+  while cell != start {
+    cells.push(cell);
+    cell = after(cell);
+  }
+  Some(cells)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::file::GameData;
+  use crate::level::Level;
+  use crate::world::Rules;
+
+  fn hard(strategy: Strategy) -> Bot {
+    let settings = BotSettings {
+      strategy,
+      difficulty: Difficulty::Hard,
+    };
+    Bot::new(settings, 1)
+  }
+
+  /// Plays a round for up to `ticks` ticks with `bot` steering the only snek
+  fn play(world: &mut World, bot: &mut Bot, ticks: u64) {
+    for _ in 0..ticks {
+      if world.status != Status::Playing {
+        break;
+      }
+      if let Some(direction) = bot.next_direction(world, 0) {
+        world.sneks[0].turn(direction);
+      }
+      world.tick();
+    }
+  }
+
+  #[test]
+  fn takes_the_only_way_out_of_a_corner() {
+    let level: Level = "######\n#....#\n#....#\n#....#\n######".parse().unwrap();
+    for strategy in [Strategy::Greedy, Strategy::Safe, Strategy::Hamiltonian] {
+      let mut game_data = GameData::from(&World::new(level.clone(), Rules::default(), 1, 1));
+      game_data.sneks[0].positions = vec![(1, 3), (1, 2), (1, 1)];
+      game_data.sneks[0].direction = Direction::Up;
+      let mut world = World::load(game_data);
+
+      let ticks = world.sneks[0].ticks_until_step() as u64;
+      play(&mut world, &mut hard(strategy), ticks);
+      assert!(world.sneks[0].alive, "{:?} ran into the wall", strategy);
+      assert_eq!(world.sneks[0].position(), (2, 1));
+    }
+  }
+
+  #[test]
+  fn careful_strategies_survive_the_start_of_a_round() {
+    let level: Level = "\
+##################
+#................#
+#................#
+#...##......1....#
+#...##...........#
+#................#
+#..........##....#
+#.1........##....#
+#................#
+##################
+"
+    .parse()
+    .unwrap();
+    for strategy in [Strategy::Safe, Strategy::Hamiltonian] {
+      let mut world = World::new(level.clone(), Rules::default(), 1, 5);
+      play(&mut world, &mut hard(strategy), 1000);
+      assert!(world.sneks[0].alive, "{:?} died", strategy);
+      assert!(world.sneks[0].score > 0, "{:?} never ate", strategy);
+    }
+  }
+
+  #[test]
+  fn hamiltonian_bot_fills_the_board() {
+    let mut world = World::new(Level::empty(6, 6), Rules::default(), 1, 3);
+    play(&mut world, &mut hard(Strategy::Hamiltonian), 100_000);
+    assert_eq!(world.status, Status::Won);
+    assert!(world.sneks[0].alive);
+  }
+
+  #[test]
+  fn finds_cycles_around_walls() {
+    let level: Level = "\
+##########
+#........#
+#........#
+#...##...#
+#...##...#
+#........#
+#........#
+##########
+"
+    .parse()
+    .unwrap();
+    let world = World::new(level, Rules::default(), 1, 1);
+    let game_state = &world.game_state;
+    let cycle = Cycle::new(game_state).unwrap();
+
+    let cells: HashSet<Cell> = cycle.cells.iter().copied().collect();
+    assert_eq!(cells.len(), cycle.cells.len());
+    assert!(cycle.cells.len() >= 40);
+    for (i, &cell) in cycle.cells.iter().enumerate() {
+      assert!(!game_state.walls.contains(&cell) && !game_state.portals.contains_key(&cell));
+      let next = cycle.cells[(i + 1) % cycle.cells.len()];
+      assert_eq!(game_state.distance(cell, next), 1);
+    }
+  }
+}
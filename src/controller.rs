@@ -0,0 +1,135 @@
+//! Whatever steers a snek, be it a player, a recording or another machine. Each tick, before the
+//! world moves, every snek's controller is asked which way it should turn.
+
+use crate::snek::Direction;
+use crate::world::World;
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+pub trait SnekController {
+  /// Which way snek number `snek` should turn next, if at all. Turns that the snek can't make are
+  /// dropped, the same as if a player had pressed them.
+  fn next_direction(&mut self, world: &World, snek: usize) -> Option<Direction>;
+}
+
+/// A turn made at some point in a round
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct RecordedTurn {
+  /// `World::ticks` when the turn was made
+  pub tick: u64,
+  pub direction: Direction,
+}
+
+/// Plays back turns recorded by a `Recording`. Given a round started from the same seed, the snek
+/// goes exactly where it went before.
+pub struct Replay {
+  turns: Vec<RecordedTurn>,
+  next: usize,
+}
+
+impl Replay {
+  pub fn new(turns: Vec<RecordedTurn>) -> Self {
+    Self { turns, next: 0 }
+  }
+
+  /// Whether every recorded turn has been played back
+  pub fn finished(&self) -> bool {
+    self.next >= self.turns.len()
+  }
+}
+
+impl SnekController for Replay {
+  fn next_direction(&mut self, world: &World, _snek: usize) -> Option<Direction> {
+    let turn = self.turns.get(self.next).filter(|turn| turn.tick <= world.ticks)?;
+    self.next += 1;
+    Some(turn.direction)
+  }
+}
+
+/// Passes on whatever another controller says, and writes it down for a `Replay`
+pub struct Recording<C> {
+  pub controller: C,
+  pub turns: Vec<RecordedTurn>,
+}
+
+impl<C: SnekController> Recording<C> {
+  pub fn new(controller: C) -> Self {
+    Self {
+      controller,
+      turns: Vec::new(),
+    }
+  }
+}
+
+impl<C: SnekController> SnekController for Recording<C> {
+  fn next_direction(&mut self, world: &World, snek: usize) -> Option<Direction> {
+    let direction = self.controller.next_direction(world, snek)?;
+    self.turns.push(RecordedTurn {
+      tick: world.ticks,
+      direction,
+    });
+    Some(direction)
+  }
+}
+
+/// Steered from another machine: turns come in over the network whenever they arrive, and are
+/// handed over one a tick
+#[derive(Default)]
+pub struct Remote {
+  turns: VecDeque<Direction>,
+}
+
+impl Remote {
+  pub fn push(&mut self, direction: Direction) {
+    self.turns.push_back(direction);
+  }
+
+  /// Forgets any turns still waiting, for when whoever sent them has gone
+  pub fn clear(&mut self) {
+    self.turns.clear();
+  }
+}
+
+impl SnekController for Remote {
+  fn next_direction(&mut self, _world: &World, _snek: usize) -> Option<Direction> {
+    self.turns.pop_front()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::level::Level;
+  use crate::world::Rules;
+
+  fn play(world: &mut World, controller: &mut impl SnekController, ticks: u64) {
+    for _ in 0..ticks {
+      if let Some(direction) = controller.next_direction(world, 0) {
+        world.sneks[0].turn(direction);
+      }
+      world.tick();
+    }
+  }
+
+  #[test]
+  fn replays_a_recorded_round_exactly() {
+    let new_world = || World::new(Level::empty(24, 16), Rules::default(), 1, 7);
+
+    let mut world = new_world();
+    let mut recording = Recording::new(Remote::default());
+    for direction in [Direction::Left, Direction::Down, Direction::Right, Direction::Up] {
+      recording.controller.push(direction);
+      play(&mut world, &mut recording, 90);
+    }
+
+    let mut replayed = new_world();
+    let mut replay = Replay::new(recording.turns.clone());
+    play(&mut replayed, &mut replay, 4 * 90);
+
+    assert_eq!(recording.turns.len(), 4);
+    assert!(replay.finished());
+    assert_eq!(replayed.ticks, world.ticks);
+    assert_eq!(replayed.state_hash(), world.state_hash());
+  }
+}
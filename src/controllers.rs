@@ -0,0 +1,113 @@
+use crate::bindings::Action;
+
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+
+/// How far the left stick has to be pushed before it counts as a direction
+static STICK_DEAD_ZONE: i16 = 16_000;
+
+/// A plugged in controller, and where its left stick was last pointing
+struct Slot {
+  controller: GameController,
+  x: i16,
+  y: i16,
+  stick: Option<Action>,
+}
+
+/// The connected game controllers. Each one steers the snek of the player in the same slot, so the
+/// first controller plugged in steers the first snek; unplugging a controller frees up its slot
+/// for whichever is plugged in next.
+pub struct Controllers {
+  subsystem: GameControllerSubsystem,
+  slots: Vec<Option<Slot>>,
+}
+
+impl Controllers {
+  pub fn new(subsystem: GameControllerSubsystem) -> Self {
+    // Controllers that are already plugged in show up as `ControllerDeviceAdded` events too
+    Self {
+      subsystem,
+      slots: Vec::new(),
+    }
+  }
+
+  /// Handles controller events, including hot-plugging, and returns what a controller asked for,
+  /// and for which player
+  pub fn process_event(&mut self, event: &Event) -> Option<(usize, Action)> {
+    match *event {
+      Event::ControllerDeviceAdded { which, .. } => {
+        let controller = self.subsystem.open(which).ok()?;
+        let slot = Some(Slot {
+          controller,
+          x: 0,
+          y: 0,
+          stick: None,
+        });
+        match self.slots.iter_mut().find(|slot| slot.is_none()) {
+          Some(free) => *free = slot,
+          None => self.slots.push(slot),
+        }
+        None
+      }
+      Event::ControllerDeviceRemoved { which, .. } => {
+        let player = self.player(which)?;
+        self.slots[player] = None;
+        None
+      }
+      Event::ControllerButtonDown { which, button, .. } => {
+        let player = self.player(which)?;
+        let action = match button {
+          Button::Start => Action::Pause,
+          Button::DPadUp => Action::Up,
+          Button::DPadDown => Action::Down,
+          Button::DPadLeft => Action::Left,
+          Button::DPadRight => Action::Right,
+          _ => return None,
+        };
+        Some((player, action))
+      }
+      Event::ControllerAxisMotion {
+        which, axis, value, ..
+      } => {
+        let player = self.player(which)?;
+        let slot = self.slots[player].as_mut()?;
+        match axis {
+          Axis::LeftX => slot.x = value,
+          Axis::LeftY => slot.y = value,
+          _ => return None,
+        }
+
+        // Only turn as the stick is pushed into a new direction, not for every bit of motion
+        let stick = stick_direction(slot.x, slot.y);
+        if stick == slot.stick {
+          return None;
+        }
+        slot.stick = stick;
+        Some((player, stick?))
+      }
+      _ => None,
+    }
+  }
+
+  /// The slot of the controller with the joystick id `which`
+  fn player(&self, which: u32) -> Option<usize> {
+    self.slots.iter().position(|slot| {
+      slot
+        .as_ref()
+        .is_some_and(|slot| slot.controller.instance_id() == which)
+    })
+  }
+}
+
+/// Whichever way the stick is pushed furthest, if it's pushed out of the dead zone at all
+fn stick_direction(x: i16, y: i16) -> Option<Action> {
+  let (x, y) = (x as i32, y as i32);
+  if x.abs().max(y.abs()) < STICK_DEAD_ZONE as i32 {
+    None
+  } else if x.abs() > y.abs() {
+    Some(if x < 0 { Action::Left } else { Action::Right })
+  } else {
+    Some(if y < 0 { Action::Up } else { Action::Down })
+  }
+}
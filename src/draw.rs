@@ -0,0 +1,94 @@
+use snek::apple::{Apple, AppleKind};
+use snek::snek::Snek;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+/// How many pixels wide and tall each cell of a new board is, when it's sized to fit the window
+pub static BOX_SIZE: u32 = 15;
+
+/// Where the board sits in the window. The board keeps its size in cells no matter the size of the
+/// window, so it's scaled to fit and centered, leaving bars along whichever sides don't fill up.
+pub struct Viewport {
+  pub box_size: u32,
+  pub x: i32,
+  pub y: i32,
+}
+
+impl Viewport {
+  pub fn fit(cols: u32, rows: u32, window_width: u32, window_height: u32) -> Self {
+    let box_size = (window_width / cols).min(window_height / rows).max(1);
+    Self {
+      box_size,
+      x: (window_width as i32 - (cols * box_size) as i32) / 2,
+      y: (window_height as i32 - (rows * box_size) as i32) / 2,
+    }
+  }
+
+  /// The on-screen rectangle of the cell at (`x`, `y`)
+  pub fn cell(&self, x: u32, y: u32) -> Rect {
+    Rect::new(
+      self.x + (x * self.box_size) as i32,
+      self.y + (y * self.box_size) as i32,
+      self.box_size,
+      self.box_size,
+    )
+  }
+}
+
+/// The color of each player's snek, and of their score
+pub static PLAYER_COLORS: [Color; 4] = [
+  Color::RGB(0, 255, 20),
+  Color::RGB(40, 90, 255),
+  Color::RGB(255, 60, 160),
+  Color::RGB(0, 200, 200),
+];
+
+/// SDL rendering for the objects of the headless `World`
+pub trait Draw {
+  fn draw(&self, viewport: &Viewport, canvas: &mut Canvas<Window>);
+}
+
+/// A snek, drawn in the colors of the player steering it
+pub struct PlayerSnek<'s> {
+  pub snek: &'s Snek,
+  pub player: usize,
+}
+
+impl Draw for PlayerSnek<'_> {
+  fn draw(&self, viewport: &Viewport, canvas: &mut Canvas<Window>) {
+    let positions = self.snek.positions();
+    let offsets = self.snek.animation_offsets();
+    let color = PLAYER_COLORS[self.player % PLAYER_COLORS.len()];
+
+    // Fades in from the tail to the head
+    let increment: f64 = 1.0 / self.snek.len as f64;
+    let mut brightness: f64 = 0.125;
+
+    for i in 0..positions.len() {
+      let shade = |c: u8| (c as f64 * brightness).floor() as u8;
+      canvas.set_draw_color(Color::RGB(shade(color.r), shade(color.g), shade(color.b)));
+      if brightness + increment <= 1.0 {
+        brightness += increment;
+      }
+      let mut rect = viewport.cell(positions[i].0, positions[i].1);
+      rect.offset(offsets[i].0 as i32, offsets[i].1 as i32);
+      canvas.fill_rect(rect).unwrap();
+    }
+  }
+}
+
+impl Draw for Apple {
+  fn draw(&self, viewport: &Viewport, canvas: &mut Canvas<Window>) {
+    canvas.set_draw_color(match self.kind {
+      AppleKind::Red => Color::RED,
+      AppleKind::Golden => Color::RGB(255, 200, 0),
+      AppleKind::Poison => Color::RGB(130, 0, 160),
+      AppleKind::Pepper => Color::RGB(255, 120, 0),
+      AppleKind::Ice => Color::RGB(120, 200, 255),
+    });
+    canvas.fill_rect(viewport.cell(self.x, self.y)).unwrap();
+  }
+}
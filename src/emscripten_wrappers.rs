@@ -35,6 +35,7 @@ pub mod emscripten {
     }
 
     pub mod fs {
+        use std::ffi::CString;
         use std::os::raw::{c_int, c_void};
         use std::ptr::null;
 
@@ -66,10 +67,21 @@ pub mod emscripten {
         static FILE_NAME: &'static str = "save.dat";
 
         pub fn save(data: &str) {
+            save_file(FILE_NAME, data);
+        }
+
+        pub fn get_save_data() -> String {
+            load_file(FILE_NAME)
+        }
+
+        /// Stores `data` under `file_name` in the game's IndexedDB database
+        pub fn save_file(file_name: &str, data: &str) {
+            let db_name = CString::new(DB_NAME).unwrap();
+            let file_name = CString::new(file_name).unwrap();
             unsafe {
                 emscripten_idb_async_store(
-                    DB_NAME.as_ptr(),
-                    FILE_NAME.as_ptr(),
+                    db_name.as_ptr() as *const u8,
+                    file_name.as_ptr() as *const u8,
                     data.as_ptr() as *const _,
                     data.len() as i32,
                     null(),
@@ -79,14 +91,17 @@ pub mod emscripten {
             }
         }
 
-        pub fn get_save_data() -> String {
+        /// Loads whatever was stored under `file_name` in the game's IndexedDB database
+        pub fn load_file(file_name: &str) -> String {
+            let db_name = CString::new(DB_NAME).unwrap();
+            let file_name = CString::new(file_name).unwrap();
             let mut data = null();
             let mut len: i32 = 0;
 
             unsafe {
                 emscripten_idb_load(
-                    DB_NAME.as_ptr(),
-                    FILE_NAME.as_ptr(),
+                    db_name.as_ptr() as *const u8,
+                    file_name.as_ptr() as *const u8,
                     &mut data as *mut _,
                     &mut len as *mut _,
                     null(),
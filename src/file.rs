@@ -1,13 +1,11 @@
 use crate::apple::Apple;
+use crate::bot::BotSettings;
 #[cfg(target_os = "emscripten")]
 use crate::emscripten_wrappers::emscripten;
-use crate::game::Game;
-use crate::snek::Direction;
-use crate::snek::Snek;
+use crate::level::Level;
+use crate::snek::{Direction, SpeedCurve, TimedSpeedEffect, START_LEN};
+use crate::world::{random_seed, Rules, Status, World};
 
-use sdl2::render::Canvas;
-use sdl2::ttf::Font;
-use sdl2::video::Window;
 use serde::{Deserialize, Serialize};
 use serde_json;
 
@@ -20,71 +18,118 @@ use std::fs::File;
 #[cfg(not(target_os = "emscripten"))]
 use std::io::prelude::Write;
 
-static BOX_SIZE: u32 = 15;
+/// Bumped whenever the meaning of saved data changes, so that older saves can be migrated on load
+pub static SAVE_VERSION: u32 = 2;
 
-#[derive(Serialize, Deserialize)]
+// Before version 1, positions were saved in pixels, at this many pixels per cell
+static LEGACY_BOX_SIZE: u32 = 15;
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct SnekData {
   pub direction: Direction,
   pub positions: Vec<(u32, u32)>,
   pub len: u32,
-  pub tick_speed_ms: u128,
+  // Replaces `tick_speed_ms` and `step_ticks`, which never changed from what's now the start of the
+  // default curve
+  #[serde(default)]
+  pub speed: SpeedCurve,
+  #[serde(default)]
+  pub speed_effect: Option<TimedSpeedEffect>,
+  #[serde(default = "default_alive")]
+  pub alive: bool,
+  #[serde(default)]
+  pub score: u32,
+  #[serde(default)]
+  pub hunger: u64,
+  #[serde(default)]
+  pub bot: Option<BotSettings>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct GameData {
+  #[serde(default)]
+  pub version: u32,
+  #[serde(default)]
+  pub cols: u32,
+  #[serde(default)]
+  pub rows: u32,
   pub sneks: Vec<SnekData>,
   pub apples: Vec<Apple>,
+  #[serde(default)]
+  pub status: Status,
+  #[serde(default)]
+  pub rules: Rules,
+  // Saves from before the RNG was seeded carry on from a fresh seed
+  #[serde(default = "random_seed")]
+  pub seed: u64,
+  #[serde(default)]
+  pub rng_word_pos: u128,
+  #[serde(default)]
+  pub ticks: u64,
+  #[serde(default)]
+  pub level: Option<Level>,
+}
+
+impl GameData {
+  /// Brings a save from an older version of the game up to date. Saves that didn't record the size
+  /// of their board are fit onto a `cols` x `rows` one.
+  fn migrate(mut self, cols: u32, rows: u32) -> Self {
+    if self.version < 1 {
+      // Pixels to cells; the old wrap-around could also leave sneks one cell off the board
+      let to_cell = |(x, y): (u32, u32)| (x / LEGACY_BOX_SIZE % cols, y / LEGACY_BOX_SIZE % rows);
+
+      self.cols = cols;
+      self.rows = rows;
+      for snek in &mut self.sneks {
+        for position in &mut snek.positions {
+          *position = to_cell(*position);
+        }
+      }
+      for apple in &mut self.apples {
+        (apple.x, apple.y) = to_cell((apple.x, apple.y));
+      }
+    }
+
+    if self.version < 2 {
+      // Scores used to just be how much a snek had grown
+      for snek in &mut self.sneks {
+        snek.score = snek.len.saturating_sub(START_LEN);
+      }
+    }
+
+    self.version = SAVE_VERSION;
+    self
+  }
+}
+
+// Saves from before sneks could die are always alive
+fn default_alive() -> bool {
+  true
 }
 
 #[cfg(not(target_os = "emscripten"))]
-pub fn load<'a>(canvas: &Canvas<Window>, font: Font<'a, 'a>) -> Game<'a> {
+pub fn load(cols: u32, rows: u32) -> World {
   let project_dirs = ProjectDirs::from("", "ElykDeer", "snek").unwrap();
   let path = project_dirs.data_dir().join("save.dat");
 
   if let Ok(file_reader) = File::open(&path) {
     if let Ok(game_data) = serde_json::from_reader::<_, GameData>(file_reader) {
-      let sneks = game_data
-        .sneks
-        .into_iter()
-        .map(|s| Snek::load(s.direction, s.positions, s.len, s.tick_speed_ms))
-        .collect();
-
-      return Game::load(
-        canvas.window().size().0,
-        canvas.window().size().1,
-        font,
-        BOX_SIZE,
-        sneks,
-        game_data.apples,
-      );
+      return World::load(game_data.migrate(cols, rows));
     }
   }
 
-  Game::new(canvas, font, BOX_SIZE)
+  World::new(Level::empty(cols, rows), Rules::default(), 1, random_seed())
 }
 
 #[cfg(target_os = "emscripten")]
-pub fn load<'a>(canvas: &Canvas<Window>, font: Font<'a, 'a>) -> Game<'a> {
+pub fn load(cols: u32, rows: u32) -> World {
   let save_data = emscripten::fs::get_save_data();
 
   if let Ok(game_data) = serde_json::from_str::<GameData>(&save_data) {
-    let sneks = game_data
-      .sneks
-      .into_iter()
-      .map(|s| Snek::load(s.direction, s.positions, s.len, s.tick_speed_ms))
-      .collect();
-
-    return Game::load(
-      canvas.window().size().0,
-      canvas.window().size().1,
-      font,
-      BOX_SIZE,
-      sneks,
-      game_data.apples,
-    );
+    return World::load(game_data.migrate(cols, rows));
   }
 
-  Game::new(canvas, font, BOX_SIZE)
+  World::new(Level::empty(cols, rows), Rules::default(), 1, random_seed())
 }
 
 #[cfg(not(target_os = "emscripten"))]
@@ -115,3 +160,95 @@ pub fn save<'a>(game_data: GameData) {
     emscripten::fs::save(&game_json);
   }
 }
+
+/// Reads the settings file `name` from the config directory, if there is one
+#[cfg(not(target_os = "emscripten"))]
+pub fn load_config(name: &str) -> Option<String> {
+  let project_dirs = ProjectDirs::from("", "ElykDeer", "snek")?;
+  std::fs::read_to_string(project_dirs.config_dir().join(name)).ok()
+}
+
+#[cfg(target_os = "emscripten")]
+pub fn load_config(name: &str) -> Option<String> {
+  let contents = emscripten::fs::load_file(name);
+  (!contents.is_empty()).then_some(contents)
+}
+
+/// Writes the settings file `name` to the config directory
+#[cfg(not(target_os = "emscripten"))]
+pub fn save_config(name: &str, contents: &str) {
+  let project_dirs = ProjectDirs::from("", "ElykDeer", "snek").unwrap();
+  let path = project_dirs.config_dir();
+
+  if create_dir_all(path).is_ok() {
+    if let Ok(mut file_writer) = File::create(path.join(name)) {
+      file_writer
+        .write_all(contents.as_bytes())
+        .expect("Unable to save settings!");
+    } else {
+      println!("Failed to create settings file {}!", name);
+    }
+  } else {
+    println!("Failed to create config directory {:?}!", path);
+  }
+}
+
+#[cfg(target_os = "emscripten")]
+pub fn save_config(name: &str, contents: &str) {
+  emscripten::fs::save_file(name, contents);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn migrates_pixel_positions_from_version_0() {
+    let save = r#"{
+      "sneks": [{ "direction": "Up", "positions": [[30, 60], [30, 45], [300, 45]], "len": 8 }],
+      "apples": [{ "x": 150, "y": 15, "eaten": false }]
+    }"#;
+    let game_data = serde_json::from_str::<GameData>(save).unwrap().migrate(20, 10);
+
+    assert_eq!(game_data.version, SAVE_VERSION);
+    assert_eq!((game_data.cols, game_data.rows), (20, 10));
+    let snek = &game_data.sneks[0];
+    // The last segment had wrapped one cell off the right edge
+    assert_eq!(snek.positions, vec![(2, 4), (2, 3), (0, 3)]);
+    assert!(snek.alive);
+    assert_eq!(snek.score, 5);
+    assert_eq!((game_data.apples[0].x, game_data.apples[0].y), (10, 1));
+  }
+
+  #[test]
+  fn migrates_scores_from_version_1() {
+    let save = r#"{
+      "version": 1,
+      "cols": 30,
+      "rows": 20,
+      "sneks": [{ "direction": "Left", "positions": [[5, 5], [4, 5], [3, 5]], "len": 10 }],
+      "apples": [{ "x": 12, "y": 7, "eaten": false }]
+    }"#;
+    let game_data = serde_json::from_str::<GameData>(save).unwrap().migrate(80, 53);
+
+    assert_eq!(game_data.version, SAVE_VERSION);
+    assert_eq!((game_data.cols, game_data.rows), (30, 20));
+    assert_eq!(game_data.sneks[0].positions, vec![(5, 5), (4, 5), (3, 5)]);
+    assert_eq!(game_data.sneks[0].score, 7);
+    assert_eq!((game_data.apples[0].x, game_data.apples[0].y), (12, 7));
+  }
+
+  #[test]
+  fn leaves_current_saves_alone() {
+    let mut game_data = GameData::from(&World::new(Level::empty(12, 8), Rules::default(), 2, 3));
+    game_data.sneks[0].score = 42;
+    let migrated = game_data.clone().migrate(80, 53);
+
+    assert_eq!((migrated.cols, migrated.rows), (12, 8));
+    assert_eq!(migrated.sneks[0].score, 42);
+    assert_eq!(
+      World::load(migrated).state_hash(),
+      World::load(game_data).state_hash()
+    );
+  }
+}
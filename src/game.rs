@@ -1,130 +1,302 @@
-use crate::apple::Apple;
-use crate::file::{save, GameData};
-use crate::snek::Snek;
+use crate::bindings::{Action, Bindings, Rebinding, REBIND_KEY};
+use crate::draw::{Draw, PlayerSnek, Viewport, PLAYER_COLORS};
+use crate::steering::{controllers, online_controllers, Device, Steering};
+use crate::touch::{dpad_buttons, Touch};
 
-use sdl2::event::Event;
+use snek::file::{save, GameData};
+use snek::lockstep::Lockstep;
+use snek::server::{ClientMessage, ServerConnection};
+use snek::snek::Direction;
+use snek::world::{Status, World, TICKS_PER_SECOND};
+
+use sdl2::event::{Event, WindowEvent};
 use sdl2::keyboard::Keycode;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
-use sdl2::render::{Canvas, TextureCreator, TextureQuery};
+use sdl2::render::{BlendMode, Canvas, Texture, TextureCreator, TextureQuery};
 use sdl2::ttf::Font;
 use sdl2::video::{Window, WindowContext};
 
 use std::time::Instant;
 
-pub struct GameState {
-    pub window_width: u32,
-    pub window_height: u32,
-    pub box_size: u32,
-    pub last_save_time: Instant,
-}
-
 pub struct Game<'a> {
-    game_state: GameState,
+    world: World,
     font: Font<'a, 'a>,
+    hud_font: Font<'a, 'a>,
     paused: bool,
-
-    sneks: Vec<Snek>,
-    apples: Vec<Apple>,
+    last_save_time: Instant,
+    viewport: Viewport,
+    touch: Touch,
+    bindings: Bindings,
+    /// The rebinding screen, while it's open
+    rebinding: Option<Rebinding>,
+    /// Whatever steers each snek that's steered from this machine, along with the snek it steers
+    controllers: Vec<(usize, Box<dyn Steering>)>,
+    /// The connection to the other players, when the round is played across machines
+    lockstep: Option<Lockstep>,
+    /// The connection to the server, when the round is played on one. The world is then just
+    /// the server's latest snapshot.
+    server: Option<ServerConnection>,
+    /// Whether this machine only watches the server's round, with nothing to steer. That doesn't
+    /// change if the server goes away; the last snapshot just stays up.
+    spectating: bool,
+    /// Whether the round was shared across the network until the connection was lost. It then
+    /// stays frozen as it was last seen, and is never saved over the round played on this machine.
+    lost_connection: bool,
+    window_size: (u32, u32),
 }
 
 impl<'a> Game<'a> {
-    pub fn new(canvas: &Canvas<Window>, font: Font<'a, 'a>, box_size: u32) -> Self {
-        let (window_width, window_height) = canvas.window().size();
+    pub fn new(
+        world: World,
+        font: Font<'a, 'a>,
+        hud_font: Font<'a, 'a>,
+        touch: Touch,
+        bindings: Bindings,
+        window_width: u32,
+        window_height: u32,
+    ) -> Self {
+        let viewport = Viewport::fit(
+            world.game_state.cols,
+            world.game_state.rows,
+            window_width,
+            window_height,
+        );
 
-        Self {
-            game_state: GameState {
-                window_width,
-                window_height,
-                box_size,
-                last_save_time: Instant::now(),
-            },
+        let controllers = controllers(&world);
 
+        Self {
+            world,
             font,
+            hud_font,
             paused: false,
-
-            sneks: vec![Snek::new(window_width, window_height, box_size)],
-            apples: vec![Apple::new(window_width, window_height, box_size)],
+            last_save_time: Instant::now(),
+            viewport,
+            touch,
+            bindings,
+            rebinding: None,
+            controllers,
+            lockstep: None,
+            server: None,
+            spectating: false,
+            lost_connection: false,
+            window_size: (window_width, window_height),
         }
     }
 
-    pub fn load(
-        window_width: u32,
-        window_height: u32,
-        font: Font<'a, 'a>,
-        box_size: u32,
-        sneks: Vec<Snek>,
-        apples: Vec<Apple>,
-    ) -> Self {
-        Self {
-            game_state: GameState {
-                window_width,
-                window_height,
-                box_size,
-                last_save_time: Instant::now(),
-            },
+    pub fn world(&self) -> &World {
+        &self.world
+    }
 
-            font,
-            paused: false,
+    /// Plays the round in lockstep with other players across the network, from now on
+    pub fn play_online(&mut self, lockstep: Lockstep) {
+        self.controllers = online_controllers(lockstep.player);
+        self.lockstep = Some(lockstep);
+    }
 
-            sneks,
-            apples,
-        }
+    /// Draws the round being played on a server, and sends it this machine's turns, unless it's
+    /// only there to watch
+    pub fn play_on_server(&mut self, server: ServerConnection) {
+        self.spectating = server.player.is_none();
+        self.controllers = match server.player {
+            Some(player) => online_controllers(player),
+            None => Vec::new(),
+        };
+        self.server = Some(server);
+    }
+
+    /// Whether the round is shared with other players across the network, in which case it's
+    /// theirs as much as it is ours, so it can't be paused or saved
+    pub fn online(&self) -> bool {
+        self.lockstep.is_some() || self.server.is_some() || self.spectating || self.lost_connection
+    }
+
+    /// Refits the board to a window that's changed size
+    pub fn resize(&mut self, window_width: u32, window_height: u32) {
+        self.window_size = (window_width, window_height);
+        self.viewport = Viewport::fit(
+            self.world.game_state.cols,
+            self.world.game_state.rows,
+            window_width,
+            window_height,
+        );
     }
 
     pub fn process_event(&mut self, event: &Event) {
+        let (window_width, window_height) = self.window_size;
+        if !self.spectating {
+            if let Some(action) = self.touch.process_event(event, window_width, window_height) {
+                return self.act(Device::Touch, action);
+            }
+        }
+
         match event {
-            Event::KeyDown {
-                keycode: Some(Keycode::Space),
+            Event::Window {
+                win_event: WindowEvent::Resized(width, height),
+                ..
+            }
+            | Event::Window {
+                win_event: WindowEvent::SizeChanged(width, height),
                 ..
             } => {
-                self.paused = !self.paused;
-                // self.timer = Instant::now(); // TODO : Reset all snek timers?
+                self.resize(*width as u32, *height as u32);
             }
-            _ => {
-                for snek in &mut self.sneks {
-                    snek.process_event(&event);
+            Event::KeyDown {
+                keycode: Some(keycode),
+                repeat: false,
+                ..
+            } if !self.spectating => {
+                if let Some(rebinding) = &mut self.rebinding {
+                    if *keycode == Keycode::Escape {
+                        self.rebinding = None;
+                    } else if *keycode != REBIND_KEY && rebinding.press(*keycode) {
+                        self.bindings = self.rebinding.take().unwrap().draft;
+                        self.bindings.save();
+                    }
+                } else if *keycode == REBIND_KEY {
+                    let players = self.world.sneks.iter().filter(|snek| snek.bot.is_none());
+                    self.rebinding = Some(Rebinding::new(&self.bindings, players.count()));
+                } else {
+                    let actions: Vec<(usize, Action)> = self.bindings.actions(*keycode).collect();
+                    for (player, action) in actions {
+                        self.act(Device::Keyboard(player), action);
+                    }
                 }
             }
+            _ => (),
         }
     }
 
-    pub fn tick(&mut self) {
-        // Check if game's been saved, save
-        if (Instant::now() - self.game_state.last_save_time).as_secs() > 5 {
-            save(self.into());
-        }
-
-        if !self.paused {
-            // Check interactions between game objects
-            //   If a snake has eaten an apple:
-            'apples: for apple in &mut self.apples {
-                if !apple.eaten {
-                    for snek in &mut self.sneks {
-                        if snek.position() == (apple.x, apple.y) {
-                            snek.len += 1;
-                            apple.eaten = true;
-                            continue 'apples;
-                        }
+    /// Does what a player asked for, from whichever device they asked with. Turns are passed on
+    /// to the sneks' controllers. Spectators can't ask for anything, and nor can anyone once the
+    /// connection is lost.
+    pub fn act(&mut self, device: Device, action: Action) {
+        if self.spectating || self.lost_connection {
+            return;
+        }
+        match action {
+            Action::Pause => self.press_start(),
+            Action::Restart => {
+                if self.world.status != Status::Playing {
+                    self.restart();
+                }
+            }
+            _ => {
+                if let Some(direction) = action.direction() {
+                    for (_, controller) in &mut self.controllers {
+                        controller.press(device, direction);
                     }
                 }
             }
+        }
+    }
+
+    /// Asks each controller which way its snek should go, and steers the sneks that way
+    fn steer(&mut self) {
+        let turns: Vec<(usize, Direction)> = self
+            .controllers
+            .iter_mut()
+            .filter_map(|(snek, controller)| {
+                let direction = controller.next_direction(&self.world, *snek)?;
+                Some((*snek, direction))
+            })
+            .collect();
+        for (snek, direction) in turns {
+            self.turn(snek, direction);
+        }
+    }
 
-            for snek in &mut self.sneks {
-                snek.tick(&self.game_state);
+    /// Steers the given snek. Online, that's always this machine's snek.
+    fn turn(&mut self, snek: usize, direction: Direction) {
+        if let Some(lockstep) = &mut self.lockstep {
+            return lockstep.turn(direction);
+        }
+        if let Some(server) = &mut self.server {
+            if server.send(&ClientMessage::Turn(direction)).is_err() {
+                self.disconnect();
             }
+            return;
+        }
 
-            for apple in &mut self.apples {
-                apple.tick(&self.game_state);
+        if let Some(snek) = self.world.sneks.get_mut(snek) {
+            snek.turn(direction);
+        }
+    }
+
+    /// Starts a new round once this one's over, and otherwise pauses or unpauses
+    fn press_start(&mut self) {
+        if self.world.status != Status::Playing {
+            self.restart();
+        } else if !self.online() {
+            self.paused = !self.paused;
+        }
+    }
+
+    /// Starts a new round. Online, every player's round restarts together, a few ticks from now.
+    fn restart(&mut self) {
+        if let Some(lockstep) = &mut self.lockstep {
+            lockstep.restart();
+        } else if let Some(server) = &mut self.server {
+            if server.send(&ClientMessage::Restart).is_err() {
+                self.disconnect();
             }
+        } else {
+            // Bots are seeded from the round, so they're rebuilt to play the new round afresh
+            self.world.restart();
+            self.controllers = controllers(&self.world);
         }
     }
 
-    pub fn tick_animations(&mut self) {
-        if !self.paused {
-            for snek in &mut self.sneks {
-                snek.tick_animations();
+    /// Gives up on the server, leaving the round frozen as it was last seen
+    fn disconnect(&mut self) {
+        eprintln!("Lost the connection to the server");
+        self.server = None;
+        self.lost_connection = true;
+    }
+
+    /// Advances the game by one fixed game tick; while paused, the world doesn't move at all
+    pub fn tick(&mut self) {
+        self.steer();
+
+        // Online, the world only ticks once everyone's turns for the tick are in
+        if let Some(lockstep) = &mut self.lockstep {
+            if let Err(e) = lockstep.tick(&mut self.world) {
+                eprintln!("Lost the connection to the other players: {}", e);
+                self.lockstep = None;
+                self.lost_connection = true;
+            }
+            return;
+        }
+
+        // On a server, the world only changes when a new snapshot comes in
+        if let Some(server) = &mut self.server {
+            match server.latest_snapshot() {
+                Ok(Some(game_data)) => {
+                    let resized = (game_data.cols, game_data.rows)
+                        != (self.world.game_state.cols, self.world.game_state.rows);
+                    self.world = World::load(game_data);
+                    if resized {
+                        let (window_width, window_height) = self.window_size;
+                        self.resize(window_width, window_height);
+                    }
+                }
+                Ok(None) => (),
+                Err(_) => self.disconnect(),
             }
+            return;
+        }
+        if self.spectating || self.lost_connection {
+            return;
+        }
+
+        // Check if game's been saved, save
+        if (Instant::now() - self.last_save_time).as_secs() > 5 {
+            save(self.world().into());
+            self.last_save_time = Instant::now();
+        }
+
+        if !self.paused && self.rebinding.is_none() {
+            self.world.tick();
         }
     }
 
@@ -133,24 +305,153 @@ impl<'a> Game<'a> {
         canvas: &mut Canvas<Window>,
         texture_creator: &TextureCreator<WindowContext>,
     ) {
-        // Draw Score
-        let score = self.sneks.iter().fold(0, |acc, snek| acc + snek.len - 3);
-        let text_texture = texture_creator
-            .create_texture_from_surface(
-                self.font
-                    .render(&format!("{}", score))
-                    .blended(Color::BLACK)
-                    .unwrap(),
+        // Draw the board, with the bars around it in grey
+        canvas.set_draw_color(Color::RGB(200, 200, 200));
+        canvas.clear();
+        let board = Rect::new(
+            self.viewport.x,
+            self.viewport.y,
+            self.world.game_state.cols * self.viewport.box_size,
+            self.world.game_state.rows * self.viewport.box_size,
+        );
+        canvas.set_draw_color(Color::WHITE);
+        canvas.fill_rect(board).unwrap();
+
+        // Solid edges get drawn as a wall just outside the board
+        if !self.world.game_state.wraps() {
+            canvas.set_draw_color(Color::RGB(60, 60, 60));
+            for inset in 1..=2 {
+                canvas
+                    .draw_rect(Rect::new(
+                        board.x() - inset,
+                        board.y() - inset,
+                        board.width() + 2 * inset as u32,
+                        board.height() + 2 * inset as u32,
+                    ))
+                    .unwrap();
+            }
+        }
+
+        // Draw the level's walls
+        canvas.set_draw_color(Color::RGB(60, 60, 60));
+        for &(x, y) in &self.world.game_state.walls {
+            canvas.fill_rect(self.viewport.cell(x, y)).unwrap();
+        }
+
+        // Draw portals as hollow squares, so they don't look like anything that can be eaten
+        canvas.set_draw_color(Color::RGB(0, 90, 255));
+        for &(x, y) in self.world.game_state.portals.keys() {
+            let cell = self.viewport.cell(x, y);
+            let border = (cell.width() / 5).max(1);
+            for offset in 0..border {
+                let ring = Rect::new(
+                    cell.x() + offset as i32,
+                    cell.y() + offset as i32,
+                    cell.width() - offset * 2,
+                    cell.height() - offset * 2,
+                );
+                canvas.draw_rect(ring).unwrap();
+            }
+        }
+
+        // Draw each player's score in their color, the first player's furthest right
+        let mut right = canvas.window().size().0 as i32 - 2;
+        for (player, snek) in self.world.sneks.iter().enumerate() {
+            let text_texture = self.render_colored_text(
+                &self.font,
+                texture_creator,
+                &self.score_label(player, snek.score),
+                PLAYER_COLORS[player % PLAYER_COLORS.len()],
+            );
+            let TextureQuery { width, height, .. } = text_texture.query();
+            canvas
+                .copy(
+                    &text_texture,
+                    None,
+                    Rect::new(right - width as i32, 2, width, height),
+                )
+                .unwrap();
+            right -= width as i32 + 20;
+        }
+
+        // Draw speed, in cells per second
+        if let Some(snek) = self.world.sneks.first() {
+            let speed = TICKS_PER_SECOND as f64 / snek.step_ticks() as f64;
+            let text_texture = self.render_text(
+                &self.hud_font,
+                texture_creator,
+                &format!("{:.1} cells/s", speed),
+            );
+            let TextureQuery { width, height, .. } = text_texture.query();
+            canvas
+                .copy(&text_texture, None, Rect::new(2, 2, width, height))
+                .unwrap();
+        }
+
+        // Draw whatever the mode has on the clock
+        let mode = self.world.game_state.rules.mode;
+        let clock = match (
+            mode.ticks_left(&self.world),
+            mode.ticks_until_starving(&self.world, 0),
+        ) {
+            (Some(ticks), _) => Some(format!("{}s left", seconds(ticks))),
+            (None, Some(ticks)) => Some(format!("starving in {}s", seconds(ticks))),
+            (None, None) => None,
+        };
+        if let Some(clock) = clock {
+            let text_texture = self.render_text(&self.font, texture_creator, &clock);
+            let TextureQuery { width, height, .. } = text_texture.query();
+            canvas
+                .copy(
+                    &text_texture,
+                    None,
+                    Rect::new(
+                        (canvas.window().size().0 as i32 - width as i32) / 2,
+                        2,
+                        width,
+                        height,
+                    ),
+                )
+                .unwrap();
+        }
+
+        // Draw seed, so that an interesting round can be replayed with `--seed`
+        let text_texture = self.render_text(
+            &self.hud_font,
+            texture_creator,
+            &format!("seed {}", self.world.seed),
+        );
+        let TextureQuery { width, height, .. } = text_texture.query();
+        canvas
+            .copy(
+                &text_texture,
+                None,
+                Rect::new(
+                    2,
+                    canvas.window().size().1 as i32 - height as i32 - 2,
+                    width,
+                    height,
+                ),
             )
             .unwrap();
+
+        // Draw how to get to the rebinding screen, across from the seed. Spectators have no keys
+        // to change, so they're reminded that they're only watching instead.
+        let hint = if self.spectating {
+            "Spectating".to_string()
+        } else {
+            format!("{} to change keys", REBIND_KEY.name())
+        };
+        let text_texture = self.render_text(&self.hud_font, texture_creator, &hint);
         let TextureQuery { width, height, .. } = text_texture.query();
+        let (window_width, window_height) = canvas.window().size();
         canvas
             .copy(
                 &text_texture,
                 None,
                 Rect::new(
-                    canvas.window().size().0 as i32 - width as i32 - 2,
-                    2,
+                    window_width as i32 - width as i32 - 2,
+                    window_height as i32 - height as i32 - 2,
                     width,
                     height,
                 ),
@@ -158,35 +459,192 @@ impl<'a> Game<'a> {
             .unwrap();
 
         // Draw sneks
-        for snek in &self.sneks {
-            snek.draw(&self.game_state, canvas);
+        for (player, snek) in self.world.sneks.iter().enumerate() {
+            PlayerSnek { snek, player }.draw(&self.viewport, canvas);
         }
 
         // Draw apples
-        for apple in &self.apples {
-            apple.draw(&self.game_state, canvas);
+        for apple in self
+            .world
+            .apples
+            .iter()
+            .filter(|apple| !apple.eaten && apple.visible(&self.world.game_state))
+        {
+            apple.draw(&self.viewport, canvas);
+        }
+
+        // Draw the on-screen D-pad, faintly so that the board shows through it
+        if self.touch.dpad && !self.spectating {
+            let (window_width, window_height) = canvas.window().size();
+            canvas.set_blend_mode(BlendMode::Blend);
+            canvas.set_draw_color(Color::RGBA(0, 0, 0, 60));
+            for (_, button) in dpad_buttons(window_width, window_height) {
+                canvas.fill_rect(button).unwrap();
+            }
+            canvas.set_blend_mode(BlendMode::None);
+        }
+
+        // Draw the result of the round over everything else
+        if self.world.status != Status::Playing {
+            let (window_width, window_height) = canvas.window().size();
+            let mut lines = vec![match self.world.status {
+                Status::Won => "Board Cleared!".to_string(),
+                Status::TimeUp => "Time's Up!".to_string(),
+                _ => "Game Over".to_string(),
+            }];
+            for (player, snek) in self.world.sneks.iter().enumerate() {
+                lines.push(format!("Score: {}", self.score_label(player, snek.score)));
+            }
+            match self.bindings.keys(0, Action::Restart).first() {
+                _ if self.spectating => lines.push("Waiting for the players to restart".to_string()),
+                Some(key) => lines.push(format!("Press {} to restart", key)),
+                None => (),
+            }
+
+            let mut y = window_height as i32 / 2 - (lines.len() as i32 * 50) / 2;
+            for line in &lines {
+                let text_texture = self.render_text(&self.font, texture_creator, line);
+                let TextureQuery { width, height, .. } = text_texture.query();
+                canvas
+                    .copy(
+                        &text_texture,
+                        None,
+                        Rect::new(
+                            (window_width as i32 - width as i32) / 2,
+                            y,
+                            width,
+                            height,
+                        ),
+                    )
+                    .unwrap();
+                y += height as i32;
+            }
+        }
+
+        // Draw anything wrong with the connection across the middle of the board
+        let problem = self.lockstep.as_ref().and_then(|lockstep| match lockstep.desync {
+            Some(tick) => Some(format!("Out of sync with the other players at tick {}", tick)),
+            None => lockstep
+                .timed_out()
+                .then(|| "Waiting for the other players...".to_string()),
+        });
+        let problem = problem.or_else(|| {
+            self.lost_connection.then(|| "Lost the connection".to_string())
+        });
+        if let Some(problem) = problem {
+            let (window_width, window_height) = canvas.window().size();
+            let text_texture = self.render_text(&self.font, texture_creator, &problem);
+            let TextureQuery { width, height, .. } = text_texture.query();
+            canvas
+                .copy(
+                    &text_texture,
+                    None,
+                    Rect::new(
+                        (window_width as i32 - width as i32) / 2,
+                        (window_height as i32 - height as i32) / 2,
+                        width,
+                        height,
+                    ),
+                )
+                .unwrap();
+        }
+
+        if let Some(rebinding) = &self.rebinding {
+            self.draw_rebinding(rebinding, canvas, texture_creator);
         }
     }
-}
 
-impl<'a> Into<GameData> for Game<'a> {
-    fn into(self) -> GameData {
-        let sneks = self.sneks.iter().map(|s| s.into()).collect();
+    /// Lists every action the rebinding screen asks about, with the keys they're bound to, and
+    /// points out the one it's asking about now
+    fn draw_rebinding(
+        &self,
+        rebinding: &Rebinding,
+        canvas: &mut Canvas<Window>,
+        texture_creator: &TextureCreator<WindowContext>,
+    ) {
+        let (window_width, window_height) = canvas.window().size();
+        canvas.set_blend_mode(BlendMode::Blend);
+        canvas.set_draw_color(Color::RGBA(255, 255, 255, 220));
+        canvas
+            .fill_rect(Rect::new(0, 0, window_width, window_height))
+            .unwrap();
+        canvas.set_blend_mode(BlendMode::None);
+
+        let mut lines = vec![
+            "Press a key for each action".to_string(),
+            "(Backspace keeps the current keys, Escape cancels)".to_string(),
+        ];
+        for (step, &(player, action)) in rebinding.steps.iter().enumerate() {
+            let who = match action.direction() {
+                Some(_) if self.world.sneks.len() > 1 => format!("P{} ", player + 1),
+                _ => String::new(),
+            };
+            let keys = rebinding.draft.keys(player, action).join(", ");
+            let pointer = if step == rebinding.step { "> " } else { "" };
+            lines.push(format!("{}{}{}: {}", pointer, who, action.name(), keys));
+        }
+
+        let line_height = self.hud_font.height();
+        let mut y = (window_height as i32 - lines.len() as i32 * line_height) / 2;
+        for line in &lines {
+            let text_texture = self.render_text(&self.hud_font, texture_creator, line);
+            let TextureQuery { width, height, .. } = text_texture.query();
+            canvas
+                .copy(
+                    &text_texture,
+                    None,
+                    Rect::new(
+                        (window_width as i32 - width as i32) / 2,
+                        y,
+                        width,
+                        height,
+                    ),
+                )
+                .unwrap();
+            y += line_height;
+        }
+    }
 
-        GameData {
-            sneks,
-            apples: self.apples.clone(),
+    /// A score, with who it belongs to when there's more than one player
+    fn score_label(&self, player: usize, score: u32) -> String {
+        if self.world.sneks.len() == 1 {
+            format!("{}", score)
+        } else if self.world.sneks[player].bot.is_some() {
+            format!("P{} (bot) {}", player + 1, score)
+        } else {
+            format!("P{} {}", player + 1, score)
         }
     }
+
+    fn render_text<'t>(
+        &self,
+        font: &Font,
+        texture_creator: &'t TextureCreator<WindowContext>,
+        text: &str,
+    ) -> Texture<'t> {
+        self.render_colored_text(font, texture_creator, text, Color::BLACK)
+    }
+
+    fn render_colored_text<'t>(
+        &self,
+        font: &Font,
+        texture_creator: &'t TextureCreator<WindowContext>,
+        text: &str,
+        color: Color,
+    ) -> Texture<'t> {
+        texture_creator
+            .create_texture_from_surface(font.render(text).blended(color).unwrap())
+            .unwrap()
+    }
 }
 
-impl<'a> Into<GameData> for &mut Game<'a> {
-    fn into(self) -> GameData {
-        let sneks = self.sneks.iter().map(|s| s.into()).collect();
+/// Whole seconds in `ticks`, rounded up so that a clock only shows 0 once it's actually run out
+fn seconds(ticks: u64) -> u64 {
+    ticks.div_ceil(TICKS_PER_SECOND as u64)
+}
 
-        GameData {
-            sneks,
-            apples: self.apples.clone(),
-        }
+impl<'a> From<&Game<'a>> for GameData {
+    fn from(game: &Game<'a>) -> Self {
+        game.world().into()
     }
 }
@@ -16,7 +16,7 @@ pub fn frame_wait(t1: Instant) {
 
 #[cfg(target_os = "emscripten")]
 pub fn frame_wait(t1: Instant) {
-  use crate::emscripten_wrappers::emscripten;
+  use snek::emscripten_wrappers::emscripten;
 
   let frame_time = (Instant::now() - t1).as_millis();
   if frame_time < u32::MAX as u128 {
@@ -0,0 +1,319 @@
+//! Levels are drawn as plain text, one character per cell of the board:
+//!
+//! ```text
+//! ##########
+//! #>.......#
+//! #...##...#
+//! #...##.@.#
+//! #........#
+//! ##########
+//! ```
+//!
+//! - `#` is a wall
+//! - `.` (or a space) is an empty cell
+//! - `@` is an apple the round starts with
+//! - `^`, `v`, `<` and `>` are where sneks start, and which way they're headed
+//! - `0` to `9` are portals. Each digit appears exactly twice, and a snek that heads into one end
+//!   comes out of the other, still headed the same way.
+//!
+//! The board is as wide as the longest line and as tall as the number of lines.
+
+use crate::snek::{Direction, START_LEN};
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Spawn {
+  pub x: u32,
+  pub y: u32,
+  pub direction: Direction,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Level {
+  pub cols: u32,
+  pub rows: u32,
+  #[serde(default)]
+  pub walls: Vec<(u32, u32)>,
+  #[serde(default)]
+  pub spawns: Vec<Spawn>,
+  #[serde(default)]
+  pub apples: Vec<(u32, u32)>,
+  /// Pairs of cells that lead to each other
+  #[serde(default)]
+  pub portals: Vec<((u32, u32), (u32, u32))>,
+}
+
+impl Level {
+  /// A plain rectangle of a board, with sneks starting in the middle
+  pub fn empty(cols: u32, rows: u32) -> Self {
+    Self {
+      cols,
+      rows,
+      walls: Vec::new(),
+      spawns: Vec::new(),
+      apples: Vec::new(),
+      portals: Vec::new(),
+    }
+  }
+
+  /// Where the `n`th of `count` sneks starts. Levels without enough spawn points line sneks up
+  /// across the middle of the board, evenly spaced, each nudged to the nearest cell that's clear of
+  /// walls, portals, apples and the sneks before it, and that has room to head off from.
+  pub fn spawn(&self, n: usize, count: usize) -> Spawn {
+    if let Some(&spawn) = self.spawns.get(n) {
+      return spawn;
+    }
+
+    let taken: HashSet<(u32, u32)> = (0..n)
+      .map(|m| self.spawn(m, count))
+      .map(|spawn| (spawn.x, spawn.y))
+      .chain(self.walls.iter().copied())
+      .chain(self.apples.iter().copied())
+      .chain(self.portals.iter().flat_map(|&(a, b)| [a, b]))
+      .collect();
+
+    let (x, y) = (
+      self.cols * (2 * n as u32 + 1) / (2 * count as u32),
+      self.rows / 2,
+    );
+    let mut cells: Vec<(u32, u32)> = (0..self.rows)
+      .flat_map(|y| (0..self.cols).map(move |x| (x, y)))
+      .filter(|cell| !taken.contains(cell))
+      .collect();
+    cells.sort_by_key(|&(cx, cy)| cx.abs_diff(x) + cy.abs_diff(y));
+
+    let directions = [
+      Direction::Up,
+      Direction::Down,
+      Direction::Left,
+      Direction::Right,
+    ];
+    cells
+      .into_iter()
+      .find_map(|(x, y)| {
+        let room = |direction| {
+          let mut cell = (x, y);
+          (0..START_LEN).all(|_| match self.next_to(cell.0, cell.1, direction) {
+            Some(next) if !taken.contains(&next) => {
+              cell = next;
+              true
+            }
+            _ => false,
+          })
+        };
+        let direction = directions.into_iter().find(|&direction| room(direction))?;
+        Some(Spawn { x, y, direction })
+      })
+      .unwrap_or(Spawn {
+        x,
+        y,
+        direction: Direction::Up,
+      })
+  }
+
+  /// Every portal cell mapped to its partner, both ways around
+  pub fn portal_links(&self) -> HashMap<(u32, u32), (u32, u32)> {
+    self
+      .portals
+      .iter()
+      .flat_map(|&(a, b)| [(a, b), (b, a)])
+      .collect()
+  }
+
+  /// The cell next to `(x, y)` in `direction`, if that's still on the board
+  fn next_to(&self, x: u32, y: u32, direction: Direction) -> Option<(u32, u32)> {
+    let (x, y) = match direction {
+      Direction::Up => (x, y.checked_sub(1)?),
+      Direction::Down => (x, y + 1),
+      Direction::Left => (x.checked_sub(1)?, y),
+      Direction::Right => (x + 1, y),
+    };
+    (x < self.cols && y < self.rows).then_some((x, y))
+  }
+
+  /// Makes sure every snek can start where the level says it does: on the board, not on a wall
+  /// or a portal, and not headed straight into a wall.
+  pub fn check(&self) -> Result<(), String> {
+    let portals = self.portal_links();
+    for spawn in &self.spawns {
+      let (x, y) = (spawn.x, spawn.y);
+      let at = format!("line {}, column {}", y + 1, x + 1);
+      if x >= self.cols || y >= self.rows {
+        return Err(format!("the snek at {} starts off the board", at));
+      }
+      if self.walls.contains(&(x, y)) {
+        return Err(format!("the snek at {} starts on a wall", at));
+      }
+      if portals.contains_key(&(x, y)) {
+        return Err(format!("the snek at {} starts on a portal", at));
+      }
+      if self
+        .next_to(x, y, spawn.direction)
+        .is_some_and(|cell| self.walls.contains(&cell))
+      {
+        return Err(format!("the snek at {} starts headed into a wall", at));
+      }
+    }
+    Ok(())
+  }
+
+  pub fn load(path: &Path) -> Result<Self, String> {
+    std::fs::read_to_string(path)
+      .map_err(|e| format!("couldn't read level {:?}: {}", path, e))?
+      .parse()
+      .map_err(|e| format!("couldn't load level {:?}: {}", path, e))
+  }
+}
+
+impl FromStr for Level {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut lines: Vec<&str> = s.lines().collect();
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+      lines.pop();
+    }
+
+    let mut level = Level::empty(
+      lines.iter().map(|line| line.chars().count()).max().unwrap_or(0) as u32,
+      lines.len() as u32,
+    );
+    if level.cols == 0 || level.rows == 0 {
+      return Err("the level is empty".to_string());
+    }
+
+    let mut portal_ends: [Vec<(u32, u32)>; 10] = Default::default();
+
+    for (y, line) in lines.iter().enumerate() {
+      for (x, c) in line.chars().enumerate() {
+        let (x, y) = (x as u32, y as u32);
+        let spawn = |direction| Spawn { x, y, direction };
+
+        match c {
+          '.' | ' ' => (),
+          '#' => level.walls.push((x, y)),
+          '@' => level.apples.push((x, y)),
+          '^' => level.spawns.push(spawn(Direction::Up)),
+          'v' => level.spawns.push(spawn(Direction::Down)),
+          '<' => level.spawns.push(spawn(Direction::Left)),
+          '>' => level.spawns.push(spawn(Direction::Right)),
+          '0'..='9' => portal_ends[c as usize - '0' as usize].push((x, y)),
+          _ => {
+            return Err(format!(
+              "unknown cell `{}` at line {}, column {}",
+              c,
+              y + 1,
+              x + 1
+            ))
+          }
+        }
+      }
+    }
+
+    for (digit, ends) in portal_ends.iter().enumerate() {
+      match ends[..] {
+        [] => (),
+        [a, b] => level.portals.push((a, b)),
+        _ => {
+          return Err(format!(
+            "portal `{}` appears {} times (expected exactly 2)",
+            digit,
+            ends.len()
+          ))
+        }
+      }
+    }
+
+    level.check()?;
+    Ok(level)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_every_kind_of_cell() {
+    let level: Level = "\
+#######
+#>..@.#
+#.1.^ #
+#..1.<
+#######
+"
+    .parse()
+    .unwrap();
+
+    assert_eq!((level.cols, level.rows), (7, 5));
+    assert_eq!(level.walls.len(), 7 + 2 + 2 + 1 + 7);
+    assert!(level.walls.contains(&(0, 3)));
+    assert!(!level.walls.contains(&(6, 3)));
+    assert_eq!(level.apples, vec![(4, 1)]);
+    assert_eq!(level.portals, vec![((2, 2), (3, 3))]);
+
+    let spawns: Vec<_> = level
+      .spawns
+      .iter()
+      .map(|spawn| (spawn.x, spawn.y, spawn.direction))
+      .collect();
+    assert_eq!(
+      spawns,
+      vec![
+        (1, 1, Direction::Right),
+        (4, 2, Direction::Up),
+        (5, 3, Direction::Left),
+      ]
+    );
+    assert_eq!(level.portal_links()[&(3, 3)], (2, 2));
+  }
+
+  #[test]
+  fn rejects_broken_levels() {
+    let error = |s: &str| s.parse::<Level>().unwrap_err();
+
+    assert_eq!(error("\n\n"), "the level is empty");
+    assert_eq!(error("#.x\n"), "unknown cell `x` at line 1, column 3");
+    assert_eq!(
+      error("1..1\n.1..\n"),
+      "portal `1` appears 3 times (expected exactly 2)"
+    );
+    assert_eq!(error("..7.\n"), "portal `7` appears 1 times (expected exactly 2)");
+    assert_eq!(
+      error("...\n.>#\n...\n"),
+      "the snek at line 2, column 2 starts headed into a wall"
+    );
+  }
+
+  #[test]
+  fn spawns_sneks_without_spawn_points_clear_of_walls() {
+    let level = Level::empty(12, 8);
+    let spawn = level.spawn(1, 3);
+    assert_eq!((spawn.x, spawn.y, spawn.direction), (6, 4, Direction::Up));
+
+    let level: Level = "\
+######
+#....#
+#.##.#
+#.##.#
+#....#
+######
+"
+    .parse()
+    .unwrap();
+    for n in 0..2 {
+      let spawn = level.spawn(n, 2);
+      assert!(!level.walls.contains(&(spawn.x, spawn.y)));
+      let ahead = level.next_to(spawn.x, spawn.y, spawn.direction).unwrap();
+      assert!(!level.walls.contains(&ahead));
+    }
+    assert_ne!(
+      (level.spawn(0, 2).x, level.spawn(0, 2).y),
+      (level.spawn(1, 2).x, level.spawn(1, 2).y)
+    );
+  }
+}
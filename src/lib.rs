@@ -0,0 +1,13 @@
+pub mod apple;
+pub mod bot;
+pub mod controller;
+pub mod file;
+pub mod level;
+pub mod lockstep;
+pub mod mode;
+pub mod server;
+pub mod snek;
+pub mod world;
+
+#[cfg(target_os = "emscripten")]
+pub mod emscripten_wrappers;
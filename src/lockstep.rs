@@ -0,0 +1,541 @@
+//! Multiplayer across machines, in deterministic lockstep over UDP.
+//!
+//! One player hosts, and everyone else joins them. Once everyone's joined, the host sends out the
+//! round as it stands, and from then on the only thing that crosses the network is which way each
+//! player turned on each tick. Every peer runs the same simulation from the same start with the
+//! same turns, so every peer sees the same round.
+//!
+//! Turns go through the host: each peer sends the host its own turns, and the host sends everyone
+//! a frame of all the players' turns for each tick, once it has all of them. A peer only ticks its
+//! world once it has the frame for that tick. Turns are played a few ticks after they're made,
+//! which leaves time for them to get around before anyone needs them.
+//!
+//! Nothing is ever acknowledged on its own. Messages carry everything the other end hasn't
+//! confirmed yet, so a lost message is made up for by the next one.
+//!
+//! Each peer also sends hashes of its world after recent ticks; if two copies of the round ever
+//! drift apart, it's noticed and the round stops.
+
+use crate::file::GameData;
+use crate::snek::Direction;
+use crate::world::{Status, World};
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// How many ticks after it's made that a turn is played
+pub static INPUT_DELAY: u64 = 6;
+
+/// A peer that hasn't been heard from in this long has probably gone
+pub static TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often a joining peer asks the host to let it in
+static JOIN_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Most frames (or inputs) that are sent in one message
+static MAX_BATCH: usize = 32;
+
+/// How many of its latest state hashes a peer sends along with each message
+static HASHES_SENT: usize = 8;
+
+/// How many ticks' worth of state hashes a peer keeps to compare against
+static HASHES_KEPT: u64 = 240;
+
+/// Big enough for any message this module sends
+static MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// What one player did on one tick
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Input {
+  pub turns: Vec<Direction>,
+  /// Whether they asked for a new round, which only happens if this one's over
+  #[serde(default)]
+  pub restart: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Message {
+  /// A peer asking the host to let it in
+  Join,
+  /// The host letting a peer in: which snek it steers, and the round everyone starts from
+  Welcome { player: usize, game: Box<GameData> },
+  /// A peer's inputs for every tick from `first_tick` on that the host hasn't confirmed, and the
+  /// last tick it has every frame up to
+  Inputs {
+    player: usize,
+    first_tick: u64,
+    inputs: Vec<Input>,
+    ack: u64,
+    hashes: Vec<(u64, u64)>,
+  },
+  /// Everyone's inputs for every tick from `first_tick` on that the peer hasn't confirmed
+  Frames {
+    first_tick: u64,
+    frames: Vec<Vec<Input>>,
+    hashes: Vec<(u64, u64)>,
+  },
+}
+
+enum Role {
+  Host {
+    /// Each joined peer's address; peer `i` steers snek `i + 1`
+    peers: Vec<SocketAddr>,
+    /// The last tick each peer has every frame up to
+    acks: Vec<u64>,
+    /// What's come in for ticks that don't have a frame yet, by player
+    inputs: BTreeMap<u64, Vec<Option<Input>>>,
+    /// The first tick without a frame yet
+    next_frame: u64,
+    /// The round as it was sent out, for peers whose welcome got lost
+    game: Box<GameData>,
+  },
+  Join {
+    host: SocketAddr,
+  },
+}
+
+/// This peer's end of a lockstep round
+pub struct Lockstep {
+  socket: UdpSocket,
+  role: Role,
+  /// The snek this peer steers
+  pub player: usize,
+  players: usize,
+  /// The last tick that's been played. Ticks are counted on across restarts, unlike
+  /// `World::ticks`, which starts over with each new round and stands still once a round is over.
+  ticks: u64,
+
+  /// What's been done since the last tick's input was scheduled
+  pending: Input,
+  /// This peer's inputs that aren't part of a frame yet, by the tick they're played on
+  local: BTreeMap<u64, Input>,
+  next_scheduled: u64,
+
+  /// Everyone's inputs for the ticks this peer has yet to play
+  frames: BTreeMap<u64, Vec<Input>>,
+  /// This peer's world's hash after each recent tick
+  hashes: BTreeMap<u64, u64>,
+
+  buffer: Vec<u8>,
+  last_heard: Instant,
+  /// The first tick that another peer's world disagreed with this one's about
+  pub desync: Option<u64>,
+}
+
+impl Lockstep {
+  /// Hosts `world` on `port`, waiting until a peer has joined for each of its other sneks. Returns
+  /// the round everyone starts from, which replaces `world`.
+  pub fn host(port: u16, world: &World) -> io::Result<(Self, World)> {
+    let socket = UdpSocket::bind(("0.0.0.0", port))?;
+    let game = Box::new(GameData::from(world));
+    let (players, start) = (world.sneks.len(), world.ticks);
+
+    let mut peers: Vec<SocketAddr> = Vec::new();
+    let mut buffer = vec![0; MAX_MESSAGE_SIZE];
+    while peers.len() + 1 < players {
+      println!(
+        "Waiting for {} more player(s) on port {}...",
+        players - 1 - peers.len(),
+        port
+      );
+      let (len, from) = socket.recv_from(&mut buffer)?;
+      if let Ok(Message::Join) = serde_json::from_slice(&buffer[..len]) {
+        let player = match peers.iter().position(|&peer| peer == from) {
+          Some(i) => i + 1,
+          None => {
+            peers.push(from);
+            peers.len()
+          }
+        };
+        send(&socket, from, &welcome(player, &game))?;
+      }
+    }
+
+    let lockstep = Self::new(
+      socket,
+      Role::Host {
+        acks: vec![start; peers.len()],
+        peers,
+        inputs: BTreeMap::new(),
+        next_frame: start + 1,
+        game: game.clone(),
+      },
+      0,
+      players,
+      start,
+    )?;
+    Ok((lockstep, World::load(*game)))
+  }
+
+  /// Joins the round hosted at `address`, waiting until the host lets this peer in
+  pub fn join(address: &str) -> io::Result<(Self, World)> {
+    let host = address
+      .to_socket_addrs()?
+      .next()
+      .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such host"))?;
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+    socket.set_read_timeout(Some(JOIN_INTERVAL))?;
+
+    println!("Joining {}...", host);
+    let mut buffer = vec![0; MAX_MESSAGE_SIZE];
+    loop {
+      send(&socket, host, &Message::Join)?;
+      let len = match socket.recv_from(&mut buffer) {
+        Ok((len, from)) if from == host => len,
+        Ok(_) => continue,
+        Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+          continue
+        }
+        Err(e) => return Err(e),
+      };
+
+      if let Ok(Message::Welcome { player, game }) = serde_json::from_slice(&buffer[..len]) {
+        let world = World::load(*game);
+        let (players, start) = (world.sneks.len(), world.ticks);
+        let lockstep = Self::new(socket, Role::Join { host }, player, players, start)?;
+        return Ok((lockstep, world));
+      }
+    }
+  }
+
+  /// A peer's end of a round that's already played up to tick `start`
+  fn new(
+    socket: UdpSocket,
+    role: Role,
+    player: usize,
+    players: usize,
+    start: u64,
+  ) -> io::Result<Self> {
+    socket.set_nonblocking(true)?;
+
+    // Nobody can have turned in time for the first few ticks
+    let local = (start + 1..=start + INPUT_DELAY)
+      .map(|tick| (tick, Input::default()))
+      .collect();
+
+    Ok(Self {
+      socket,
+      role,
+      player,
+      players,
+      ticks: start,
+      pending: Input::default(),
+      local,
+      next_scheduled: start + INPUT_DELAY + 1,
+      frames: BTreeMap::new(),
+      hashes: BTreeMap::new(),
+      buffer: vec![0; MAX_MESSAGE_SIZE],
+      last_heard: Instant::now(),
+      desync: None,
+    })
+  }
+
+  /// Turns this peer's snek, a few ticks from now
+  pub fn turn(&mut self, direction: Direction) {
+    self.pending.turns.push(direction);
+  }
+
+  /// Asks for a new round, a few ticks from now, if this one's over by then
+  pub fn restart(&mut self) {
+    self.pending.restart = true;
+  }
+
+  /// Whether the other peers have gone quiet for so long that they've probably gone
+  pub fn timed_out(&self) -> bool {
+    self.last_heard.elapsed() > TIMEOUT
+  }
+
+  /// Trades inputs with the other peers, and ticks `world` if everyone's inputs for its next tick
+  /// are in. Returns whether it ticked.
+  pub fn tick(&mut self, world: &mut World) -> io::Result<bool> {
+    self.receive()?;
+
+    // Play whatever was done since the last tick a few ticks from now
+    if self.next_scheduled <= self.ticks + 1 + INPUT_DELAY {
+      self
+        .local
+        .insert(self.next_scheduled, std::mem::take(&mut self.pending));
+      self.next_scheduled += 1;
+    }
+
+    if let Role::Host { inputs, .. } = &mut self.role {
+      for (tick, input) in std::mem::take(&mut self.local) {
+        inputs
+          .entry(tick)
+          .or_insert_with(|| vec![None; self.players])[0] = Some(input);
+      }
+    }
+    self.make_frames();
+    self.send()?;
+
+    if self.desync.is_some() {
+      return Ok(false);
+    }
+    let Some(frame) = self.frames.get(&(self.ticks + 1)).cloned() else {
+      return Ok(false);
+    };
+
+    if frame.iter().any(|input| input.restart) && world.status != Status::Playing {
+      world.restart();
+    }
+    for (snek, input) in world.sneks.iter_mut().zip(&frame) {
+      for &direction in &input.turns {
+        snek.turn(direction);
+      }
+    }
+    world.tick();
+    self.ticks += 1;
+
+    // Joining peers are done with a frame once it's played; the host keeps its frames until every
+    // peer has them
+    if let Role::Join { .. } = self.role {
+      self.frames = self.frames.split_off(&(self.ticks + 1));
+    }
+
+    self.hashes.insert(self.ticks, world.state_hash());
+    self.hashes = self
+      .hashes
+      .split_off(&self.ticks.saturating_sub(HASHES_KEPT));
+
+    Ok(true)
+  }
+
+  /// Takes in every message that's arrived
+  fn receive(&mut self) -> io::Result<()> {
+    loop {
+      let (len, from) = match self.socket.recv_from(&mut self.buffer) {
+        Ok(received) => received,
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+        // A peer that's gone can make the next receive fail on some platforms; that's what the
+        // timeout is for
+        Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+        Err(e) => return Err(e),
+      };
+      let Ok(message) = serde_json::from_slice::<Message>(&self.buffer[..len]) else {
+        continue;
+      };
+
+      match (&mut self.role, message) {
+        (Role::Host { peers, game, .. }, Message::Join) => {
+          // A peer that never got its welcome
+          if let Some(i) = peers.iter().position(|&peer| peer == from) {
+            send(&self.socket, from, &welcome(i + 1, game))?;
+          }
+        }
+
+        (
+          Role::Host {
+            peers,
+            acks,
+            inputs: waiting,
+            next_frame,
+            ..
+          },
+          Message::Inputs {
+            player,
+            first_tick,
+            inputs,
+            ack,
+            hashes,
+          },
+        ) => {
+          if player == 0 || peers.get(player - 1) != Some(&from) {
+            continue;
+          }
+          self.last_heard = Instant::now();
+          acks[player - 1] = acks[player - 1].max(ack);
+
+          for (tick, input) in (first_tick..).zip(inputs) {
+            if tick >= *next_frame {
+              waiting
+                .entry(tick)
+                .or_insert_with(|| vec![None; self.players])[player] = Some(input);
+            }
+          }
+          check_hashes(&self.hashes, &hashes, &mut self.desync);
+        }
+
+        (
+          Role::Join { host },
+          Message::Frames {
+            first_tick,
+            frames,
+            hashes,
+          },
+        ) => {
+          if from != *host {
+            continue;
+          }
+          self.last_heard = Instant::now();
+
+          for (tick, frame) in (first_tick..).zip(frames) {
+            if tick > self.ticks {
+              self.frames.insert(tick, frame);
+            }
+          }
+          check_hashes(&self.hashes, &hashes, &mut self.desync);
+        }
+
+        _ => (),
+      }
+    }
+  }
+
+  /// Turns every tick that the host has all of the inputs for into a frame
+  fn make_frames(&mut self) {
+    let Role::Host {
+      inputs, next_frame, ..
+    } = &mut self.role
+    else {
+      return;
+    };
+
+    while let Some(frame) = inputs.get(next_frame) {
+      if frame.iter().any(|input| input.is_none()) {
+        break;
+      }
+      let frame = inputs.remove(next_frame).unwrap().into_iter().flatten().collect();
+      self.frames.insert(*next_frame, frame);
+      *next_frame += 1;
+    }
+  }
+
+  /// Sends the other end everything it hasn't confirmed yet
+  fn send(&mut self) -> io::Result<()> {
+    let hashes: Vec<(u64, u64)> = self
+      .hashes
+      .iter()
+      .rev()
+      .take(HASHES_SENT)
+      .map(|(&tick, &hash)| (tick, hash))
+      .collect();
+
+    match &self.role {
+      Role::Host { peers, acks, .. } => {
+        // The host keeps each frame until everyone's confirmed it, as well as until it's played
+        for (&peer, &ack) in peers.iter().zip(acks) {
+          let first_tick = ack + 1;
+          let frames: Vec<Vec<Input>> = self
+            .frames
+            .range(first_tick..)
+            .take(MAX_BATCH)
+            .map(|(_, frame)| frame.clone())
+            .collect();
+          let message = Message::Frames {
+            first_tick,
+            frames,
+            hashes: hashes.clone(),
+          };
+          send(&self.socket, peer, &message)?;
+        }
+
+        let confirmed = acks.iter().copied().min().unwrap_or(self.ticks).min(self.ticks);
+        self.frames = self.frames.split_off(&(confirmed + 1));
+      }
+
+      Role::Join { host } => {
+        // Everything up to the last unbroken frame is confirmed, and has the inputs in it
+        let mut ack = self.ticks;
+        while self.frames.contains_key(&(ack + 1)) {
+          ack += 1;
+        }
+        self.local = self.local.split_off(&(ack + 1));
+
+        let first_tick = self.local.keys().next().copied().unwrap_or(ack + 1);
+        let message = Message::Inputs {
+          player: self.player,
+          first_tick,
+          inputs: self.local.values().take(MAX_BATCH).cloned().collect(),
+          ack,
+          hashes,
+        };
+        send(&self.socket, *host, &message)?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+fn welcome(player: usize, game: &GameData) -> Message {
+  Message::Welcome {
+    player,
+    game: Box::new(game.clone()),
+  }
+}
+
+fn send(socket: &UdpSocket, to: SocketAddr, message: &Message) -> io::Result<()> {
+  let bytes = serde_json::to_vec(message).map_err(io::Error::from)?;
+  match socket.send_to(&bytes, to) {
+    // A full send buffer just means this message is lost, which the next one makes up for
+    Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+    result => result.map(|_| ()),
+  }
+}
+
+/// Records the first tick where any of `theirs` disagrees with `ours`
+fn check_hashes(ours: &BTreeMap<u64, u64>, theirs: &[(u64, u64)], desync: &mut Option<u64>) {
+  for &(tick, hash) in theirs {
+    if ours.get(&tick).is_some_and(|&our_hash| our_hash != hash) {
+      *desync = Some(desync.map_or(tick, |desync| desync.min(tick)));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::level::Level;
+  use crate::world::{EdgeMode, Rules};
+
+  use std::thread;
+
+  /// A host and a peer that's joined it over loopback, with their copies of a round that ends
+  /// as soon as both sneks run into the edge
+  fn pair(port: u16) -> ((Lockstep, World), (Lockstep, World)) {
+    let rules = Rules {
+      edges: EdgeMode::Walls,
+      ..Rules::default()
+    };
+    let world = World::new(Level::empty(10, 8), rules, 2, 3);
+    let host = thread::spawn(move || Lockstep::host(port, &world).unwrap());
+    let joined = Lockstep::join(&format!("127.0.0.1:{}", port)).unwrap();
+    (host.join().unwrap(), joined)
+  }
+
+  /// Ticks both peers until `done` says to stop, and fails if that takes too long
+  fn play_until(peers: &mut [&mut (Lockstep, World)], done: impl Fn(&World) -> bool) {
+    for _ in 0..10_000 {
+      if peers.iter().all(|(_, world)| done(world)) {
+        return;
+      }
+      for (lockstep, world) in peers.iter_mut() {
+        lockstep.tick(world).unwrap();
+        assert_eq!(lockstep.desync, None);
+      }
+      thread::sleep(Duration::from_millis(1));
+    }
+    panic!("the peers stopped ticking");
+  }
+
+  #[test]
+  fn restarts_a_round_on_every_peer() {
+    let (mut host, mut peer) = pair(47_801);
+    play_until(&mut [&mut host, &mut peer], |world| world.status != Status::Playing);
+    let ended = host.1.ticks;
+    assert_eq!(peer.1.ticks, ended);
+
+    host.0.restart();
+    play_until(&mut [&mut host, &mut peer], |world| {
+      world.status == Status::Playing && world.ticks > 0 && world.ticks < ended
+    });
+
+    // And then the new round plays out the same on both
+    play_until(&mut [&mut host, &mut peer], |world| world.status != Status::Playing);
+    play_until(&mut [&mut host, &mut peer], |_| true);
+    assert_eq!(host.1.state_hash(), peer.1.state_hash());
+  }
+}
@@ -1,22 +1,36 @@
-mod apple;
-mod file;
+mod bindings;
+mod controllers;
+mod draw;
 mod game;
 mod helpers;
-mod snek;
+mod options;
+mod steering;
+mod touch;
 
 #[cfg(target_os = "emscripten")]
-mod emscripten_wrappers;
-#[cfg(target_os = "emscripten")]
-use emscripten_wrappers::emscripten;
+use snek::emscripten_wrappers::emscripten;
+use snek::file;
+use snek::level::Level;
+use snek::lockstep::Lockstep;
+use snek::server::ServerConnection;
+use snek::world::{random_seed, Rules, World, TICKS_PER_SECOND};
+
+use bindings::Bindings;
+use controllers::Controllers;
+use draw::BOX_SIZE;
+use game::Game;
+use options::Options;
+use steering::Device;
+use touch::{Touch, DEFAULT_SWIPE_THRESHOLD};
 
-use sdl2;
 use sdl2::event::Event;
-use sdl2::pixels::Color;
 
 use std::path::Path;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 fn main() {
+    let options = Options::from_args();
+
     #[cfg(target_os = "emscripten")]
     let _ = sdl2::hint::set("SDL_EMSCRIPTEN_ASYNCIFY", "1");
 
@@ -42,17 +56,96 @@ fn main() {
     let font = ttf_context
         .load_font(Path::new("assets/cruft.ttf"), 50)
         .unwrap();
+    let hud_font = ttf_context
+        .load_font(Path::new("assets/cruft.ttf"), 20)
+        .unwrap();
 
-    let mut game = file::load(&canvas, font);
+    // New boards fill the window
+    let (window_width, window_height) = canvas.window().size();
+    let (cols, rows) = (window_width / BOX_SIZE, window_height / BOX_SIZE);
+    let level = options
+        .level
+        .as_ref()
+        .map(|path| Level::load(path).unwrap_or_else(|e| options::fail(&e)));
+    let mut world = if options.new_round() {
+        let mut rules = Rules::default();
+        options.apply_rules(&mut rules);
+        World::new(
+            level.unwrap_or_else(|| Level::empty(cols, rows)),
+            rules,
+            options.sneks(),
+            options.seed.unwrap_or_else(random_seed),
+        )
+    } else {
+        file::load(cols, rows)
+    };
+    options.apply(&mut world);
+
+    // Network rounds start from whatever the host sends out, once everyone's there
+    let mut lockstep = None;
+    if let Some(port) = options.host {
+        let (host, hosted) = Lockstep::host(port, &world)
+            .unwrap_or_else(|e| options::fail(&format!("couldn't host: {}", e)));
+        lockstep = Some(host);
+        world = hosted;
+    } else if let Some(address) = &options.join {
+        let (peer, joined) = Lockstep::join(address)
+            .unwrap_or_else(|e| options::fail(&format!("couldn't join {}: {}", address, e)));
+        lockstep = Some(peer);
+        world = joined;
+    }
+    let server = if let Some(address) = &options.connect {
+        Some(
+            ServerConnection::connect(address).unwrap_or_else(|e| {
+                options::fail(&format!("couldn't connect to {}: {}", address, e))
+            }),
+        )
+    } else {
+        options.watch.as_ref().map(|address| {
+            ServerConnection::watch(address)
+                .unwrap_or_else(|e| options::fail(&format!("couldn't watch {}: {}", address, e)))
+        })
+    };
+    let touch = Touch::new(
+        options.swipe_threshold.unwrap_or(DEFAULT_SWIPE_THRESHOLD),
+        options.dpad,
+    );
+    let mut game = Game::new(
+        world,
+        font,
+        hud_font,
+        touch,
+        Bindings::load(),
+        window_width,
+        window_height,
+    );
+    if let Some(lockstep) = lockstep {
+        game.play_online(lockstep);
+    }
+    if let Some(server) = server {
+        game.play_on_server(server);
+    }
 
     // After initializing everything, in the web version, we should delete the spinner from in front of the canvas
     #[cfg(target_os = "emscripten")]
     emscripten::exec("let spinner = document.getElementById('spinner'); spinner.remove();");
 
+    // The game advances in fixed ticks, as many as fit into the time since the last frame
+    let tick_length = Duration::from_secs(1) / TICKS_PER_SECOND;
+    let mut last_frame = Instant::now();
+    let mut lag = Duration::ZERO;
+
+    // Controllers are optional; without the subsystem the game is just played on the keyboard
+    let mut controllers = sdl_context.game_controller().ok().map(Controllers::new);
+
     let mut event_pump = sdl_context.event_pump().unwrap();
     'mainloop: loop {
         let t1 = Instant::now();
 
+        // Don't try to catch up on more than a quarter second (ex. after the window was dragged)
+        lag = (lag + (t1 - last_frame)).min(Duration::from_millis(250));
+        last_frame = t1;
+
         #[cfg(target_os = "emscripten")]
         if canvas.window().size().0 != emscripten::get_canvas_size().0
             || canvas.window().size().1 != emscripten::get_canvas_size().1
@@ -64,30 +157,38 @@ fn main() {
                     emscripten::get_canvas_size().1 as u32,
                 )
                 .unwrap();
+            game.resize(
+                emscripten::get_canvas_size().0,
+                emscripten::get_canvas_size().1,
+            );
         }
 
-        // Clear screen
-        canvas.set_draw_color(Color::WHITE);
-        canvas.clear();
-
         // Process this frame's events
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. } => {
-                    file::save(game.into());
+                    if !game.online() {
+                        file::save((&game).into());
+                    }
                     break 'mainloop;
                 }
-                _ => {
-                    game.process_event(&event);
-                }
+                _ => match controllers
+                    .as_mut()
+                    .and_then(|controllers| controllers.process_event(&event))
+                {
+                    Some((slot, action)) => game.act(Device::Gamepad(slot), action),
+                    None => game.process_event(&event),
+                },
             }
         }
 
         // Tick
-        game.tick();
+        while lag >= tick_length {
+            game.tick();
+            lag -= tick_length;
+        }
 
         // Draw
-        game.tick_animations();
         game.draw(&mut canvas, &texture_creator);
         canvas.present();
 
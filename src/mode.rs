@@ -0,0 +1,174 @@
+use crate::world::{Status, World, TICKS_PER_SECOND};
+
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The goal of a round: how it's scored, what ends it and what's on the clock
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum GameMode {
+  /// Eat as many apples as you can, until you crash
+  #[default]
+  Classic,
+  /// Eat as many apples as you can before the time runs out
+  TimeAttack { seconds: u32 },
+  /// Stay alive as long as you can. Apples spoil if they aren't eaten in time, and sneks starve if
+  /// they go too long without eating.
+  Survival {
+    spoil_seconds: u32,
+    starve_seconds: u32,
+  },
+}
+
+impl FromStr for GameMode {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "classic" => Ok(Self::Classic),
+      "time-attack" => Ok(Self::TimeAttack { seconds: 60 }),
+      "survival" => Ok(Self::Survival {
+        spoil_seconds: 8,
+        starve_seconds: 15,
+      }),
+      _ => Err(format!(
+        "unknown game mode `{}` (expected classic, time-attack or survival)",
+        s
+      )),
+    }
+  }
+}
+
+impl GameMode {
+  /// Points a snek scores for every apple it eats
+  pub fn apple_points(&self) -> u32 {
+    match self {
+      Self::Classic | Self::TimeAttack { .. } => 1,
+      Self::Survival { .. } => 0,
+    }
+  }
+
+  /// Runs the mode's clocks for a game tick, after everything on the board has moved
+  pub fn tick(&self, world: &mut World) {
+    match *self {
+      Self::Classic => (),
+
+      Self::TimeAttack { .. } => {
+        if self.ticks_left(world) == Some(0) {
+          world.status = Status::TimeUp;
+        }
+      }
+
+      Self::Survival { starve_seconds, .. } => {
+        let second = world.ticks.is_multiple_of(TICKS_PER_SECOND as u64);
+        for snek in world.sneks.iter_mut().filter(|snek| snek.alive) {
+          if snek.hunger >= (starve_seconds * TICKS_PER_SECOND) as u64 {
+            snek.alive = false;
+          } else if second {
+            snek.score += 1;
+          }
+        }
+      }
+    }
+  }
+
+  /// Seconds an apple lasts before it spoils, for modes where apples do. Spoiled apples go off
+  /// just like apples past the rules' `apple_lifetime`, whichever comes first.
+  pub fn spoil_seconds(&self) -> Option<u32> {
+    match *self {
+      Self::Survival { spoil_seconds, .. } => Some(spoil_seconds),
+      _ => None,
+    }
+  }
+
+  /// Ticks until the round ends, for modes that are played against the clock
+  pub fn ticks_left(&self, world: &World) -> Option<u64> {
+    match *self {
+      Self::TimeAttack { seconds } => {
+        Some(((seconds * TICKS_PER_SECOND) as u64).saturating_sub(world.ticks))
+      }
+      _ => None,
+    }
+  }
+
+  /// Ticks until the `n`th snek starves, for modes where sneks can
+  pub fn ticks_until_starving(&self, world: &World, n: usize) -> Option<u64> {
+    match *self {
+      Self::Survival { starve_seconds, .. } => Some(
+        ((starve_seconds * TICKS_PER_SECOND) as u64).saturating_sub(world.sneks.get(n)?.hunger),
+      ),
+      _ => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::level::Level;
+  use crate::world::{EdgeMode, Rules};
+
+  /// A round in `mode` for one snek on an empty board that wraps around, so it can't crash, with
+  /// no apples for it to eat
+  fn round(mode: GameMode) -> World {
+    let rules = Rules {
+      edges: EdgeMode::Wrap,
+      mode,
+      ..Rules::default()
+    };
+    let mut world = World::new(Level::empty(20, 15), rules, 1, 1);
+    world.apples.clear();
+    world
+  }
+
+  fn run(world: &mut World, seconds: u32) {
+    for _ in 0..seconds * TICKS_PER_SECOND {
+      world.tick();
+    }
+  }
+
+  #[test]
+  fn time_attack_ends_when_the_clock_runs_out() {
+    let mut world = round(GameMode::TimeAttack { seconds: 2 });
+    run(&mut world, 1);
+    assert_eq!(world.status, Status::Playing);
+    assert_eq!(
+      world.game_state.rules.mode.ticks_left(&world),
+      Some(TICKS_PER_SECOND as u64)
+    );
+
+    run(&mut world, 1);
+    assert_eq!(world.status, Status::TimeUp);
+    assert_eq!(world.ticks, 2 * TICKS_PER_SECOND as u64);
+    assert!(world.sneks[0].alive);
+  }
+
+  #[test]
+  fn survival_scores_a_point_a_second() {
+    let mut world = round(GameMode::Survival {
+      spoil_seconds: 8,
+      starve_seconds: 15,
+    });
+    run(&mut world, 3);
+    assert_eq!(world.sneks[0].score, 3);
+    assert_eq!(world.status, Status::Playing);
+  }
+
+  #[test]
+  fn survival_starves_sneks_that_dont_eat() {
+    let mut world = round(GameMode::Survival {
+      spoil_seconds: 8,
+      starve_seconds: 2,
+    });
+    run(&mut world, 1);
+    assert!(world.sneks[0].alive);
+    assert_eq!(
+      world.game_state.rules.mode.ticks_until_starving(&world, 0),
+      Some(TICKS_PER_SECOND as u64)
+    );
+
+    run(&mut world, 1);
+    assert!(!world.sneks[0].alive);
+    assert_eq!(world.status, Status::GameOver);
+    assert_eq!(world.sneks[0].score, 1);
+  }
+}
@@ -0,0 +1,230 @@
+use snek::apple::AppleTable;
+use snek::bot::{BotSettings, Difficulty, Strategy};
+use snek::mode::GameMode;
+use snek::snek::CurveShape;
+use snek::world::{CollisionRule, EdgeMode, Rules, World};
+
+use std::path::PathBuf;
+use std::process::exit;
+
+static USAGE: &str = "usage: snek [--seed <number>] [--collisions pass-through|both-die|longer-wins]
+            [--edges wrap|walls|bounce] [--level <file>]
+            [--speed-curve linear|sqrt] [--speed-floor <ticks>] [--speed-step <ticks>]
+            [--mode classic|time-attack|survival]
+            [--apples red=<weight>,golden=<weight>,poison=<weight>,pepper=<weight>,ice=<weight>]
+            [--apple-lifetime <seconds>] [--wandering-apples] [--players 1-4]
+            [--bots <number>] [--bot-strategy greedy|safe|hamiltonian]
+            [--bot-difficulty easy|normal|hard]
+            [--swipe-threshold <fraction of the screen>] [--dpad]
+            [--host <port> | --join <address>:<port> | --connect <address>:<port>
+             | --watch <address>:<port>]";
+
+/// Sneks can be steered from one keyboard by up to this many players, and there can't be more
+/// sneks than that on the board, counting bots
+pub static MAX_PLAYERS: usize = 4;
+
+/// Settings given on the command line; anything not given is left up to the save file (or defaults)
+#[derive(Default)]
+pub struct Options {
+  /// Start a new round from this seed instead of resuming the saved one
+  pub seed: Option<u64>,
+  pub collisions: Option<CollisionRule>,
+  pub edges: Option<EdgeMode>,
+  /// Start a new round on this level instead of resuming the saved one
+  pub level: Option<PathBuf>,
+  pub speed_curve: Option<CurveShape>,
+  pub speed_floor: Option<u32>,
+  pub speed_step: Option<u32>,
+  /// Start a new round in this mode instead of resuming the saved one
+  pub mode: Option<GameMode>,
+  pub apples: Option<AppleTable>,
+  pub apple_lifetime: Option<u32>,
+  pub wandering_apples: bool,
+  /// Start a new round with this many sneks instead of resuming the saved one
+  pub players: Option<usize>,
+  /// Start a new round with this many bots playing alongside the players
+  pub bots: Option<usize>,
+  /// Change how every bot plays, in new rounds and saved ones
+  pub bot_strategy: Option<Strategy>,
+  pub bot_difficulty: Option<Difficulty>,
+  pub swipe_threshold: Option<f32>,
+  /// Show an on-screen D-pad for touch screens
+  pub dpad: bool,
+  /// Host a new round on this port, for `--players` players across the network
+  pub host: Option<u16>,
+  /// Join the round hosted at this address
+  pub join: Option<String>,
+  /// Play on the server at this address
+  pub connect: Option<String>,
+  /// Watch the round on the server at this address, without playing in it
+  pub watch: Option<String>,
+}
+
+impl Options {
+  pub fn from_args() -> Self {
+    let mut options = Self::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+      match arg.as_str() {
+        "--seed" => options.seed = Some(parse(&arg, args.next())),
+        "--collisions" => options.collisions = Some(parse(&arg, args.next())),
+        "--edges" => options.edges = Some(parse(&arg, args.next())),
+        "--level" => options.level = Some(parse(&arg, args.next())),
+        "--speed-curve" => options.speed_curve = Some(parse(&arg, args.next())),
+        "--speed-floor" => options.speed_floor = Some(parse(&arg, args.next())),
+        "--speed-step" => options.speed_step = Some(parse(&arg, args.next())),
+        "--mode" => options.mode = Some(parse(&arg, args.next())),
+        "--apples" => options.apples = Some(parse(&arg, args.next())),
+        "--apple-lifetime" => {
+          let lifetime = parse(&arg, args.next());
+          if lifetime == 0 {
+            fail(&format!("{}: apples have to last at least a second", arg));
+          }
+          options.apple_lifetime = Some(lifetime);
+        }
+        "--wandering-apples" => options.wandering_apples = true,
+        "--players" => {
+          let players = parse(&arg, args.next());
+          if !(1..=MAX_PLAYERS).contains(&players) {
+            fail(&format!("{}: expected 1 to {} players", arg, MAX_PLAYERS));
+          }
+          options.players = Some(players);
+        }
+        "--bots" => options.bots = Some(parse(&arg, args.next())),
+        "--bot-strategy" => options.bot_strategy = Some(parse(&arg, args.next())),
+        "--bot-difficulty" => options.bot_difficulty = Some(parse(&arg, args.next())),
+        "--swipe-threshold" => options.swipe_threshold = Some(parse(&arg, args.next())),
+        "--dpad" => options.dpad = true,
+        "--host" => options.host = Some(parse(&arg, args.next())),
+        "--join" => options.join = Some(parse(&arg, args.next())),
+        "--connect" => options.connect = Some(parse(&arg, args.next())),
+        "--watch" => options.watch = Some(parse(&arg, args.next())),
+        "-h" | "--help" => {
+          println!("{}", USAGE);
+          exit(0);
+        }
+        _ => fail(&format!("unknown argument `{}`", arg)),
+      }
+    }
+
+    let networks = [
+      options.host.is_some(),
+      options.join.is_some(),
+      options.connect.is_some(),
+      options.watch.is_some(),
+    ];
+    if networks.iter().filter(|&&given| given).count() > 1 {
+      fail("only one of --host, --join, --connect and --watch can be used at once");
+    }
+    if options.bots.is_some() && networks.contains(&true) {
+      fail("--bots: bots can only play on this machine");
+    }
+    if options.sneks() > MAX_PLAYERS {
+      fail(&format!(
+        "--bots: at most {} sneks, players and bots together",
+        MAX_PLAYERS
+      ));
+    }
+
+    options
+  }
+
+  /// How many sneks a new round starts with. Hosted rounds are for at least two.
+  pub fn players(&self) -> usize {
+    self
+      .players
+      .unwrap_or(if self.host.is_some() { 2 } else { 1 })
+  }
+
+  /// How many sneks a new round starts with, bots included
+  pub fn sneks(&self) -> usize {
+    self.players() + self.bots.unwrap_or(0)
+  }
+
+  /// Whether to start a new round rather than resuming the saved one
+  pub fn new_round(&self) -> bool {
+    self.seed.is_some()
+      || self.level.is_some()
+      || self.mode.is_some()
+      || self.players.is_some()
+      || self.bots.is_some()
+      || self.host.is_some()
+  }
+
+  /// Overrides `rules` with any that were given on the command line
+  pub fn apply_rules(&self, rules: &mut Rules) {
+    if let Some(collisions) = self.collisions {
+      rules.collisions = collisions;
+    }
+    if let Some(edges) = self.edges {
+      rules.edges = edges;
+    }
+    if let Some(mode) = self.mode {
+      rules.mode = mode;
+    }
+    if let Some(apples) = self.apples {
+      rules.apples = apples;
+    }
+    if let Some(apple_lifetime) = self.apple_lifetime {
+      rules.apple_lifetime = Some(apple_lifetime);
+    }
+    if self.wandering_apples {
+      rules.wandering_apples = true;
+    }
+
+    if let Some(shape) = self.speed_curve {
+      rules.speed.shape = shape;
+    }
+    if let Some(floor_ticks) = self.speed_floor {
+      rules.speed.floor_ticks = floor_ticks;
+    }
+    if let Some(step_ticks) = self.speed_step {
+      rules.speed.step_ticks = step_ticks;
+    }
+  }
+
+  /// Overrides the rules of `world` with any that were given on the command line, and sets up its
+  /// bots
+  pub fn apply(&self, world: &mut World) {
+    self.apply_rules(&mut world.game_state.rules);
+
+    // Sneks that are already out on the board pick up the new curve too
+    if self.speed_curve.is_some() || self.speed_floor.is_some() || self.speed_step.is_some() {
+      for snek in &mut world.sneks {
+        snek.speed = world.game_state.rules.speed;
+      }
+    }
+
+    // New rounds put their bots after the players
+    if let Some(bots) = self.bots {
+      let players = world.sneks.len() - bots;
+      for snek in &mut world.sneks[players..] {
+        snek.bot = Some(BotSettings::default());
+      }
+    }
+    for bot in world.sneks.iter_mut().filter_map(|snek| snek.bot.as_mut()) {
+      if let Some(strategy) = self.bot_strategy {
+        bot.strategy = strategy;
+      }
+      if let Some(difficulty) = self.bot_difficulty {
+        bot.difficulty = difficulty;
+      }
+    }
+  }
+}
+
+fn parse<T: std::str::FromStr>(arg: &str, value: Option<String>) -> T
+where
+  T::Err: std::fmt::Display,
+{
+  match value {
+    Some(value) => value.parse().unwrap_or_else(|e| fail(&format!("{}: {}", arg, e))),
+    None => fail(&format!("{} needs a value", arg)),
+  }
+}
+
+pub fn fail(message: &str) -> ! {
+  eprintln!("{}\n{}", message, USAGE);
+  exit(1);
+}
@@ -0,0 +1,478 @@
+//! Rounds played on a server, which is the only one that runs the rules. Clients send it which
+//! way they turned, and it sends them snapshots of the round to draw. Any number of spectators can
+//! watch the same snapshots without steering anything.
+//!
+//! Messages go over TCP as JSON, one message per line. The level is only sent once, when a client
+//! is let in; snapshots leave it out.
+
+use crate::controller::{Remote, SnekController};
+use crate::file::GameData;
+use crate::level::Level;
+use crate::snek::Direction;
+use crate::world::{Status, World, TICKS_PER_SECOND};
+
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+/// The server sends a snapshot every this many ticks
+pub static SNAPSHOT_TICKS: u64 = (TICKS_PER_SECOND / 30) as u64;
+
+/// Spectators that haven't taken a snapshot in this long are dropped, rather than letting
+/// everything they haven't read pile up on the server
+pub static SPECTATOR_PATIENCE: u64 = (5 * TICKS_PER_SECOND) as u64;
+
+/// Messages from a client to the server
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ClientMessage {
+  /// The first thing a client sends: it wants a snek to steer
+  Join,
+  /// The first thing a spectator sends instead: it only wants to watch
+  Watch,
+  Turn(Direction),
+  /// Start a new round, if this one's over
+  Restart,
+}
+
+/// Messages from the server to a client
+#[derive(Serialize, Deserialize)]
+pub enum ServerMessage {
+  /// Which snek the client steers, and the level the round's played on
+  Welcome { player: usize, level: Level },
+  /// The spectator's been let in to watch a round on this level
+  Watching { level: Level },
+  /// The server's game is full
+  Full,
+  /// The round as it is now, without its level
+  Snapshot(Box<GameData>),
+}
+
+/// One line-delimited JSON connection, in either direction
+struct Connection {
+  reader: BufReader<TcpStream>,
+  stream: TcpStream,
+  /// Whatever's arrived of the next message
+  line: Vec<u8>,
+  /// Whatever's been sent but hasn't fit down the stream yet
+  outgoing: Vec<u8>,
+  /// Snapshots missed in a row, for still sending the one before
+  missed: u64,
+}
+
+impl Connection {
+  fn new(stream: TcpStream) -> io::Result<Self> {
+    stream.set_nodelay(true)?;
+    Ok(Self {
+      reader: BufReader::new(stream.try_clone()?),
+      stream,
+      line: Vec::new(),
+      outgoing: Vec::new(),
+      missed: 0,
+    })
+  }
+
+  /// Queues `message` to go out, and sends as much as the stream will take
+  fn send<T: Serialize>(&mut self, message: &T) -> io::Result<()> {
+    serde_json::to_writer(&mut self.outgoing, message).map_err(io::Error::from)?;
+    self.outgoing.push(b'\n');
+    self.flush()
+  }
+
+  /// Sends as much of what's queued as the stream will take. Nonblocking streams keep the rest for
+  /// next time; blocking ones send it all.
+  fn flush(&mut self) -> io::Result<()> {
+    while !self.outgoing.is_empty() {
+      match self.stream.write(&self.outgoing) {
+        Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+        Ok(sent) => {
+          self.outgoing.drain(..sent);
+        }
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+        Err(e) => return Err(e),
+      }
+    }
+    Ok(())
+  }
+
+  /// Whether everything that's been sent has gone down the stream
+  fn caught_up(&self) -> bool {
+    self.outgoing.is_empty()
+  }
+
+  /// The next whole message, if one's arrived. Nonblocking streams return `None` while there isn't
+  /// one yet; a closed stream is an error.
+  fn receive<T: for<'de> Deserialize<'de>>(&mut self) -> io::Result<Option<T>> {
+    loop {
+      match self.reader.read_until(b'\n', &mut self.line) {
+        Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+        Ok(_) if self.line.ends_with(b"\n") => {
+          let message = serde_json::from_slice(&self.line);
+          self.line.clear();
+          // Messages that can't be made sense of are skipped
+          if let Ok(message) = message {
+            return Ok(Some(message));
+          }
+        }
+        Ok(_) => (),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+        Err(e) => return Err(e),
+      }
+    }
+  }
+}
+
+/// The authoritative end of a round: it runs the world, and tells everyone how it's going
+pub struct Server {
+  listener: TcpListener,
+  pub world: World,
+  /// The client steering each snek, if there is one
+  players: Vec<Option<Connection>>,
+  /// What each snek's client has asked for and the snek hasn't been told yet
+  remotes: Vec<Remote>,
+  spectators: Vec<Connection>,
+  /// Clients that haven't said whether they're joining or watching yet
+  pending: Vec<(Connection, SocketAddr)>,
+  /// Ticks the server has run for, whether or not the round was moving
+  clock: u64,
+}
+
+impl Server {
+  pub fn bind(address: impl ToSocketAddrs, world: World) -> io::Result<Self> {
+    let listener = TcpListener::bind(address)?;
+    listener.set_nonblocking(true)?;
+    let players = world.sneks.iter().map(|_| None).collect();
+    let remotes = world.sneks.iter().map(|_| Remote::default()).collect();
+    Ok(Self {
+      listener,
+      world,
+      players,
+      remotes,
+      spectators: Vec::new(),
+      pending: Vec::new(),
+      clock: 0,
+    })
+  }
+
+  /// Whether every snek has someone steering it. The round waits until they do.
+  pub fn full(&self) -> bool {
+    self.players.iter().all(Option::is_some)
+  }
+
+  /// Lets in new clients, takes in what clients sent, advances the round by a tick if everyone's
+  /// there, and sends out a snapshot when one's due
+  pub fn tick(&mut self) -> io::Result<()> {
+    self.accept()?;
+
+    for player in 0..self.players.len() {
+      if let Some(connection) = &mut self.players[player] {
+        if connection.flush().is_err() {
+          self.leave(player);
+        }
+      }
+
+      while let Some(connection) = &mut self.players[player] {
+        match connection.receive() {
+          Ok(Some(ClientMessage::Turn(direction))) => self.remotes[player].push(direction),
+          Ok(Some(ClientMessage::Restart)) => {
+            if self.world.status != Status::Playing {
+              self.world.restart();
+            }
+          }
+          Ok(Some(_)) => (),
+          Ok(None) => break,
+          Err(_) => self.leave(player),
+        }
+      }
+
+      if let Some(direction) = self.remotes[player].next_direction(&self.world, player) {
+        self.world.sneks[player].turn(direction);
+      }
+    }
+
+    if self.full() {
+      self.world.tick();
+    }
+
+    self
+      .spectators
+      .retain_mut(|connection| connection.flush().is_ok());
+
+    self.clock += 1;
+    if self.clock.is_multiple_of(SNAPSHOT_TICKS) {
+      let game_data = GameData {
+        level: None,
+        ..GameData::from(&self.world)
+      };
+      self.broadcast(&ServerMessage::Snapshot(Box::new(game_data)));
+    }
+
+    Ok(())
+  }
+
+  /// Takes in new connections, and lets in whichever have said what they're here for
+  fn accept(&mut self) -> io::Result<()> {
+    loop {
+      let (stream, address) = match self.listener.accept() {
+        Ok(accepted) => accepted,
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+        Err(e) => return Err(e),
+      };
+      // Clients that can't be set up properly are turned away like any other dropped connection
+      let connection = stream
+        .set_nonblocking(true)
+        .and_then(|_| Connection::new(stream));
+      if let Ok(connection) = connection {
+        self.pending.push((connection, address));
+      }
+    }
+
+    for (mut connection, address) in std::mem::take(&mut self.pending) {
+      match connection.receive() {
+        Ok(Some(ClientMessage::Join)) => match self.players.iter().position(Option::is_none) {
+          Some(player) => {
+            let level = self.world.level.clone();
+            if connection.send(&ServerMessage::Welcome { player, level }).is_ok() {
+              println!("Player {} joined from {}", player + 1, address);
+              self.players[player] = Some(connection);
+            }
+          }
+          None => {
+            let _ = connection.send(&ServerMessage::Full);
+          }
+        },
+        Ok(Some(ClientMessage::Watch)) => {
+          let level = self.world.level.clone();
+          if connection.send(&ServerMessage::Watching { level }).is_ok() {
+            println!("A spectator is watching from {}", address);
+            self.spectators.push(connection);
+          }
+        }
+        Ok(Some(_)) | Err(_) => (),
+        Ok(None) => self.pending.push((connection, address)),
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Sends a snapshot to every client and spectator, dropping any that have gone. Anyone still
+  /// sending the last snapshot misses this one, since the next one will do just as well, but
+  /// spectators who keep missing them for longer than `SPECTATOR_PATIENCE` are dropped too.
+  fn broadcast(&mut self, message: &ServerMessage) {
+    for player in 0..self.players.len() {
+      let slot = self.players[player].as_mut();
+      if let Some(connection) = slot.filter(|connection| connection.caught_up()) {
+        if connection.send(message).is_err() {
+          self.leave(player);
+        }
+      }
+    }
+    self.spectators.retain_mut(|connection| {
+      if connection.caught_up() {
+        connection.missed = 0;
+        return connection.send(message).is_ok();
+      }
+      connection.missed += 1;
+      let patient = connection.missed * SNAPSHOT_TICKS < SPECTATOR_PATIENCE;
+      if !patient {
+        println!("Dropped a spectator who fell too far behind");
+      }
+      patient
+    });
+  }
+
+  /// Forgets the client steering `player`'s snek, along with any turns it sent that haven't been
+  /// made yet
+  fn leave(&mut self, player: usize) {
+    println!("Player {} left", player + 1);
+    self.players[player] = None;
+    self.remotes[player].clear();
+  }
+}
+
+/// A client's connection to a server
+pub struct ServerConnection {
+  connection: Connection,
+  /// The snek this client steers, or `None` for spectators
+  pub player: Option<usize>,
+  /// The level the server's round is played on, which snapshots don't carry
+  level: Level,
+}
+
+impl ServerConnection {
+  /// Connects to the server at `address`, waiting until it says which snek this client steers
+  pub fn connect(address: impl ToSocketAddrs) -> io::Result<Self> {
+    Self::open(address, ClientMessage::Join)
+  }
+
+  /// Connects to the server at `address` as a spectator
+  pub fn watch(address: impl ToSocketAddrs) -> io::Result<Self> {
+    Self::open(address, ClientMessage::Watch)
+  }
+
+  fn open(address: impl ToSocketAddrs, hello: ClientMessage) -> io::Result<Self> {
+    let mut connection = Connection::new(TcpStream::connect(address)?)?;
+    connection.send(&hello)?;
+    loop {
+      let (player, level) = match connection.receive()? {
+        Some(ServerMessage::Welcome { player, level }) => (Some(player), level),
+        Some(ServerMessage::Watching { level }) => (None, level),
+        Some(ServerMessage::Full) => {
+          return Err(io::Error::other("the game is full"))
+        }
+        _ => continue,
+      };
+      connection.stream.set_nonblocking(true)?;
+      return Ok(Self {
+        connection,
+        player,
+        level,
+      });
+    }
+  }
+
+  pub fn send(&mut self, message: &ClientMessage) -> io::Result<()> {
+    self.connection.send(message)
+  }
+
+  /// The latest snapshot that's come in since the last call, if there is one, with the level put
+  /// back in. Anything this client sent that hasn't gone out yet goes now.
+  pub fn latest_snapshot(&mut self) -> io::Result<Option<GameData>> {
+    self.connection.flush()?;
+    let mut latest = None;
+    while let Some(message) = self.connection.receive()? {
+      if let ServerMessage::Snapshot(game_data) = message {
+        latest = Some(*game_data);
+      }
+    }
+    Ok(latest.map(|game_data| GameData {
+      level: Some(self.level.clone()),
+      ..game_data
+    }))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::level::Level;
+  use crate::world::{EdgeMode, Rules};
+
+  use std::thread;
+  use std::time::Duration;
+
+  /// A server on loopback for a round with `players` sneks on a board that wraps around, so
+  /// nobody crashes
+  fn serve(players: usize) -> Server {
+    let rules = Rules {
+      edges: EdgeMode::Wrap,
+      ..Rules::default()
+    };
+    let world = World::new(Level::empty(20, 15), rules, players, 1);
+    Server::bind("127.0.0.1:0", world).unwrap()
+  }
+
+  /// Opens a connection with `open`, ticking `server` until it's let in (or turned away)
+  fn open(
+    server: &mut Server,
+    open: fn(SocketAddr) -> io::Result<ServerConnection>,
+  ) -> io::Result<ServerConnection> {
+    let address = server.listener.local_addr().unwrap();
+    let opening = thread::spawn(move || open(address));
+    while !opening.is_finished() {
+      server.tick().unwrap();
+      thread::sleep(Duration::from_millis(1));
+    }
+    opening.join().unwrap()
+  }
+
+  /// Ticks `server` until `done` says to stop, and fails if that takes too long
+  fn tick_until(server: &mut Server, mut done: impl FnMut(&Server) -> bool) {
+    for _ in 0..5_000 {
+      if done(server) {
+        return;
+      }
+      server.tick().unwrap();
+      thread::sleep(Duration::from_millis(1));
+    }
+    panic!("gave up waiting on the server");
+  }
+
+  #[test]
+  fn plays_a_round_with_clients_that_come_and_go() {
+    let mut server = serve(2);
+    let mut first = open(&mut server, ServerConnection::connect).unwrap();
+    assert_eq!(first.player, Some(0));
+    server.tick().unwrap();
+    // Nothing moves until everyone's there
+    assert_eq!(server.world.ticks, 0);
+
+    let mut second = open(&mut server, ServerConnection::connect).unwrap();
+    assert_eq!(second.player, Some(1));
+    assert!(server.full());
+    assert!(open(&mut server, ServerConnection::connect).is_err());
+
+    let turn = server.world.sneks[0].heading().clockwise();
+    first.send(&ClientMessage::Turn(turn)).unwrap();
+    tick_until(&mut server, |server| server.world.sneks[0].heading() == turn);
+
+    // Until the client sees the snek go the way it turned
+    tick_until(&mut server, |_| match first.latest_snapshot().unwrap() {
+      Some(snapshot) => {
+        assert!(snapshot.level.is_some());
+        assert_eq!(snapshot.sneks.len(), 2);
+        snapshot.sneks[0].direction == turn
+      }
+      None => false,
+    });
+
+    // Restarting only happens once the round's over
+    first.send(&ClientMessage::Restart).unwrap();
+    tick_until(&mut server, |server| server.world.ticks > 100);
+    server.world.status = Status::GameOver;
+    first.send(&ClientMessage::Restart).unwrap();
+    tick_until(&mut server, |server| server.world.ticks < 100);
+    assert_eq!(server.world.status, Status::Playing);
+
+    // A player who leaves takes their unmade turns with them, and the round waits for someone new
+    let turn = server.world.sneks[1].heading().clockwise();
+    second.send(&ClientMessage::Turn(turn)).unwrap();
+    drop(second);
+    tick_until(&mut server, |server| !server.full());
+    assert!(server.remotes[1].next_direction(&server.world, 1).is_none());
+    let ticks = server.world.ticks;
+    server.tick().unwrap();
+    assert_eq!(server.world.ticks, ticks);
+
+    let second = open(&mut server, ServerConnection::connect).unwrap();
+    assert_eq!(second.player, Some(1));
+  }
+
+  #[test]
+  fn drops_spectators_that_fall_behind() {
+    let mut server = serve(1);
+    let mut player = open(&mut server, ServerConnection::connect).unwrap();
+    let watching = open(&mut server, ServerConnection::watch).unwrap();
+    assert_eq!(watching.player, None);
+    assert_eq!(server.spectators.len(), 1);
+
+    // The spectator never reads anything, so once the network's full of what it hasn't read, it
+    // misses snapshots until it runs out of patience. The player keeps up all along.
+    let mut snapshots = 0;
+    for _ in 0..1_000_000 {
+      if server.spectators.is_empty() {
+        break;
+      }
+      server.tick().unwrap();
+      if player.latest_snapshot().unwrap().is_some() {
+        snapshots += 1;
+      }
+    }
+    assert!(server.spectators.is_empty());
+    assert!(server.players[0].is_some());
+    assert!(snapshots as u64 >= server.clock / SNAPSHOT_TICKS / 2);
+    drop(watching);
+  }
+}
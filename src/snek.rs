@@ -1,17 +1,13 @@
+use crate::apple::AppleKind;
+use crate::bot::BotSettings;
 use crate::file::SnekData;
-use crate::game::GameState;
-
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
+use crate::world::{EdgeMode, GameState, TICKS_PER_SECOND};
 
 use serde::{Deserialize, Serialize};
-use std::time::Instant;
+use std::collections::VecDeque;
+use std::str::FromStr;
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
 pub enum Direction {
   Up,
   Down,
@@ -19,13 +15,129 @@ pub enum Direction {
   Left,
 }
 
+impl Direction {
+  pub fn opposite(&self) -> Self {
+    match self {
+      Direction::Up => Direction::Down,
+      Direction::Down => Direction::Up,
+      Direction::Right => Direction::Left,
+      Direction::Left => Direction::Right,
+    }
+  }
+
+  pub fn clockwise(&self) -> Self {
+    match self {
+      Direction::Up => Direction::Right,
+      Direction::Right => Direction::Down,
+      Direction::Down => Direction::Left,
+      Direction::Left => Direction::Up,
+    }
+  }
+}
+
+/// How many turns can be typed ahead of the snek before the rest are ignored
+pub static MAX_QUEUED_TURNS: usize = 3;
+
+/// How long a snek is when it starts out
+pub static START_LEN: u32 = 3;
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum CurveShape {
+  /// Speeds up by the same amount every time
+  Linear,
+  /// Speeds up quickly while short, and more and more slowly as it grows
+  SquareRoot,
+}
+
+impl FromStr for CurveShape {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "linear" => Ok(Self::Linear),
+      "sqrt" => Ok(Self::SquareRoot),
+      _ => Err(format!("unknown curve shape `{}` (expected linear or sqrt)", s)),
+    }
+  }
+}
+
+/// How much faster a snek gets as it grows. Speeds are given as the number of game ticks between
+/// moves, so smaller is faster.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct SpeedCurve {
+  /// Ticks between moves at the starting length
+  pub start_ticks: u32,
+  /// The fewest ticks between moves the snek can ever reach
+  pub floor_ticks: u32,
+  /// Ticks taken off each time the snek grows by `growth_per_step`
+  pub step_ticks: u32,
+  pub growth_per_step: u32,
+  pub shape: CurveShape,
+}
+
+impl Default for SpeedCurve {
+  fn default() -> Self {
+    Self {
+      start_ticks: 8,
+      floor_ticks: 4,
+      step_ticks: 1,
+      growth_per_step: 5,
+      shape: CurveShape::Linear,
+    }
+  }
+}
+
+impl SpeedCurve {
+  /// Ticks between moves for a snek of length `len`
+  pub fn ticks_at(&self, len: u32) -> u32 {
+    let steps = len.saturating_sub(START_LEN) / self.growth_per_step.max(1);
+    let steps = match self.shape {
+      CurveShape::Linear => steps,
+      CurveShape::SquareRoot => (steps as f64).sqrt() as u32,
+    };
+
+    self
+      .start_ticks
+      .saturating_sub(self.step_ticks.saturating_mul(steps))
+      .max(self.floor_ticks)
+      .max(1)
+  }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum SpeedEffect {
+  /// Moves in two thirds the ticks
+  Fast,
+  /// Moves in one and a half times the ticks
+  Slow,
+}
+
+/// A speed effect that wears off after a number of game ticks
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct TimedSpeedEffect {
+  pub effect: SpeedEffect,
+  pub ticks_left: u64,
+}
+
+/// How long peppers and ice last
+pub static SPEED_EFFECT_SECONDS: u32 = 5;
+
 pub struct Snek {
-  timer: Instant,
-  tick_speed_ms: u128,
+  pub speed: SpeedCurve,
+  pub speed_effect: Option<TimedSpeedEffect>,
+  ticks_until_step: u32,
 
   direction: Direction,
+  /// Turns that have been asked for but not yet made, one is taken each time the snek moves
+  turns: VecDeque<Direction>,
   positions: Vec<(u32, u32)>,
   pub len: u32,
+  pub alive: bool,
+  pub score: u32,
+  /// Game ticks since this snek last ate
+  pub hunger: u64,
+  /// The kind of bot that steers this snek, if it isn't a player
+  pub bot: Option<BotSettings>,
 
   animation: SnekWiggleAnimation,
 }
@@ -92,16 +204,19 @@ impl SnekWiggleAnimation {
 }
 
 impl Snek {
-  pub fn new(window_width: u32, window_height: u32, box_size: u32) -> Self {
-    let x = window_width / box_size / 2 * box_size;
-    let y = window_height / box_size / 2 * box_size;
-
+  pub fn new(x: u32, y: u32, direction: Direction, speed: SpeedCurve) -> Self {
     Self {
-      timer: Instant::now(),
-      tick_speed_ms: 50,
-      direction: Direction::Up,
+      speed,
+      speed_effect: None,
+      ticks_until_step: speed.ticks_at(START_LEN),
+      direction,
+      turns: VecDeque::new(),
       positions: vec![(x, y), (x, y), (x, y)],
-      len: 3,
+      len: START_LEN,
+      alive: true,
+      score: 0,
+      hunger: 0,
+      bot: None,
 
       animation: SnekWiggleAnimation {
         offsets: vec![(0, 0), (0, 0), (0, 0)],
@@ -111,20 +226,24 @@ impl Snek {
     }
   }
 
-  pub fn load(
-    direction: Direction,
-    positions: Vec<(u32, u32)>,
-    len: u32,
-    tick_speed_ms: u128,
-  ) -> Self {
+  pub fn load(snek_data: SnekData) -> Self {
+    let len = snek_data.len;
+    // Sized from the cells rather than `len`, which is already shorter right after eating poison
+    let offsets = vec![(0, 0); snek_data.positions.len()];
     Self {
-      timer: Instant::now(),
-      tick_speed_ms,
-      direction,
-      positions,
+      speed: snek_data.speed,
+      speed_effect: snek_data.speed_effect,
+      ticks_until_step: snek_data.speed.ticks_at(len),
+      direction: snek_data.direction,
+      turns: VecDeque::new(),
+      positions: snek_data.positions,
       len,
+      alive: snek_data.alive,
+      score: snek_data.score,
+      hunger: snek_data.hunger,
+      bot: snek_data.bot,
       animation: SnekWiggleAnimation {
-        offsets: (0..len).into_iter().map(|_| (0, 0)).collect(),
+        offsets,
         total_wiggle: 2,
         t: 0.0,
       },
@@ -135,140 +254,427 @@ impl Snek {
     *self.positions.last().unwrap()
   }
 
-  fn move_up(&mut self, game_state: &GameState, prev_x: u32, prev_y: u32) {
+  pub fn positions(&self) -> &[(u32, u32)] {
+    &self.positions
+  }
+
+  pub fn animation_offsets(&self) -> &[(i16, i16)] {
+    &self.animation.offsets
+  }
+
+  // Each of these is the cell one over from (`prev_x`, `prev_y`), or `None` if that's off the edge
+  // of the board and the board doesn't wrap around
+
+  fn move_up(game_state: &GameState, prev_x: u32, prev_y: u32) -> Option<(u32, u32)> {
     if prev_y != 0 {
-      self.positions.push((prev_x, prev_y - game_state.box_size));
+      Some((prev_x, prev_y - 1))
     } else {
-      self.positions.push((
-        prev_x,
-        game_state.box_size * (game_state.window_height / game_state.box_size),
-      ));
+      game_state.wraps().then_some((prev_x, game_state.rows - 1))
     }
   }
 
-  fn move_down(&mut self, game_state: &GameState, prev_x: u32, prev_y: u32) {
-    if (prev_y + game_state.box_size) < game_state.window_height {
-      self.positions.push((prev_x, prev_y + game_state.box_size));
+  fn move_down(game_state: &GameState, prev_x: u32, prev_y: u32) -> Option<(u32, u32)> {
+    if (prev_y + 1) < game_state.rows {
+      Some((prev_x, prev_y + 1))
     } else {
-      self.positions.push((prev_x, 0));
+      game_state.wraps().then_some((prev_x, 0))
     }
   }
 
-  fn move_left(&mut self, game_state: &GameState, prev_x: u32, prev_y: u32) {
+  fn move_left(game_state: &GameState, prev_x: u32, prev_y: u32) -> Option<(u32, u32)> {
     if prev_x != 0 {
-      self.positions.push((prev_x - game_state.box_size, prev_y));
+      Some((prev_x - 1, prev_y))
     } else {
-      self.positions.push((
-        game_state.box_size * (game_state.window_width / game_state.box_size),
-        prev_y,
-      ));
+      game_state.wraps().then_some((game_state.cols - 1, prev_y))
     }
   }
 
-  fn move_right(&mut self, game_state: &GameState, prev_x: u32, prev_y: u32) {
-    if (prev_x + game_state.box_size) < game_state.window_width {
-      self.positions.push((prev_x + game_state.box_size, prev_y));
+  fn move_right(game_state: &GameState, prev_x: u32, prev_y: u32) -> Option<(u32, u32)> {
+    if (prev_x + 1) < game_state.cols {
+      Some((prev_x + 1, prev_y))
     } else {
-      self.positions.push((0, prev_y));
+      game_state.wraps().then_some((0, prev_y))
     }
   }
 
-  pub fn process_event(&mut self, event: &Event) {
-    match event {
-      Event::KeyDown {
-        keycode: Some(Keycode::Up),
-        ..
-      }
-      | Event::KeyDown {
-        keycode: Some(Keycode::W),
-        ..
-      } => self.direction = Direction::Up,
-
-      Event::KeyDown {
-        keycode: Some(Keycode::Down),
-        ..
-      }
-      | Event::KeyDown {
-        keycode: Some(Keycode::S),
-        ..
-      } => self.direction = Direction::Down,
-
-      Event::KeyDown {
-        keycode: Some(Keycode::Left),
-        ..
-      }
-      | Event::KeyDown {
-        keycode: Some(Keycode::A),
-        ..
-      } => self.direction = Direction::Left,
-
-      Event::KeyDown {
-        keycode: Some(Keycode::Right),
-        ..
-      }
-      | Event::KeyDown {
-        keycode: Some(Keycode::D),
-        ..
-      } => self.direction = Direction::Right,
-      _ => (),
-    }
+  /// The cell one over from `(x, y)` in `direction`, or `None` if that's off the edge of a board
+  /// that doesn't wrap around. Stepping into a portal comes out at its other end.
+  pub fn next_cell(
+    game_state: &GameState,
+    direction: Direction,
+    (x, y): (u32, u32),
+  ) -> Option<(u32, u32)> {
+    let cell = match direction {
+      Direction::Up => Self::move_up(game_state, x, y),
+      Direction::Down => Self::move_down(game_state, x, y),
+      Direction::Left => Self::move_left(game_state, x, y),
+      Direction::Right => Self::move_right(game_state, x, y),
+    }?;
+    Some(game_state.through_portal(cell))
   }
 
-  pub fn tick(&mut self, game_state: &GameState) {
-    if (Instant::now() - self.timer).as_millis() > self.tick_speed_ms {
-      let (prev_x, prev_y) = self.positions.last().unwrap().clone();
-      match &self.direction {
-        Direction::Up => self.move_up(&game_state, prev_x, prev_y),
-        Direction::Down => self.move_down(&game_state, prev_x, prev_y),
-        Direction::Left => self.move_left(&game_state, prev_x, prev_y),
-        Direction::Right => self.move_right(&game_state, prev_x, prev_y),
-      }
+  /// Turns the snek along the wall it's about to hit, preferring whichever way isn't blocked by
+  /// its own body (clockwise if neither is). Returns the cell it turned into, if there's one.
+  fn bounce(&mut self, game_state: &GameState) -> Option<(u32, u32)> {
+    let head = self.position();
+    let options: Vec<(Direction, (u32, u32))> = [
+      self.direction.clockwise(),
+      self.direction.clockwise().opposite(),
+    ]
+    .into_iter()
+    .filter_map(|direction| Some((direction, Self::next_cell(game_state, direction, head)?)))
+    .filter(|(_, cell)| !game_state.walls.contains(cell))
+    .collect();
 
-      // Don't just grow forever
-      if self.positions.len() > self.len as usize {
-        self.positions.rotate_left(1);
-        self.positions.pop();
-      }
+    let (direction, cell) = *options
+      .iter()
+      .find(|(_, cell)| !self.positions.contains(cell))
+      .or(options.first())?;
+
+    // Anything typed ahead was meant for the old heading
+    self.direction = direction;
+    self.turns.clear();
+    Some(cell)
+  }
+
+  /// Which way the snek will be going once it's made every turn that's been queued up
+  pub fn heading(&self) -> Direction {
+    *self.turns.back().unwrap_or(&self.direction)
+  }
+
+  /// How many more game ticks until the snek moves; it moves during the tick when this is 1
+  pub fn ticks_until_step(&self) -> u32 {
+    self.ticks_until_step
+  }
+
+  /// Whether the head is sitting on any other segment of this snek's body
+  pub fn collided_with_self(&self) -> bool {
+    let (head, body) = self.positions.split_last().unwrap();
+    body.contains(head)
+  }
+
+  /// Queues up a turn for one of the snek's next moves. Turns that wouldn't change the direction
+  /// the snek will be heading in at that point, or that would reverse it into itself, are dropped.
+  pub fn turn(&mut self, direction: Direction) {
+    let heading = self.heading();
+    if direction != heading
+      && direction != heading.opposite()
+      && self.turns.len() < MAX_QUEUED_TURNS
+    {
+      self.turns.push_back(direction);
     }
   }
 
-  pub fn tick_animations(&mut self) {
-    if (Instant::now() - self.timer).as_millis() > self.tick_speed_ms {
-      // TODO : THIS SHOULD NOT BE IN ANIMATIONS
-      self.timer = Instant::now();
+  /// How many game ticks the snek currently waits between moves
+  pub fn step_ticks(&self) -> u32 {
+    let ticks = self.speed.ticks_at(self.len);
+    match self.speed_effect.map(|timed| timed.effect) {
+      Some(SpeedEffect::Fast) => (ticks * 2 / 3).max(1),
+      Some(SpeedEffect::Slow) => ticks * 3 / 2,
+      None => ticks,
+    }
+  }
 
-      self.animation.tick(self.positions.as_slice());
+  /// Eats an apple of the given kind, which is worth `points` for each of its own points
+  pub fn eat(&mut self, kind: AppleKind, points: u32) {
+    self.len = self
+      .len
+      .saturating_add_signed(kind.growth())
+      .max(START_LEN);
+    self.score = self
+      .score
+      .saturating_add_signed(kind.points() * points as i32);
+    self.hunger = 0;
+
+    let effect = match kind {
+      AppleKind::Pepper => Some(SpeedEffect::Fast),
+      AppleKind::Ice => Some(SpeedEffect::Slow),
+      _ => None,
+    };
+    if let Some(effect) = effect {
+      self.speed_effect = Some(TimedSpeedEffect {
+        effect,
+        ticks_left: (SPEED_EFFECT_SECONDS * TICKS_PER_SECOND) as u64,
+      });
     }
   }
 
-  pub fn draw(&self, game_state: &GameState, canvas: &mut Canvas<Window>) {
-    let g_increment: f64 = 255.0 / self.len as f64;
-    let mut g: f64 = 32.0;
+  /// Advances the snek by one game tick, moving it if it's been `step_ticks` since its last move
+  pub fn tick(&mut self, game_state: &GameState) {
+    if !self.alive {
+      return;
+    }
+    self.hunger += 1;
 
-    for i in 0..self.positions.len() {
-      canvas.set_draw_color(Color::RGB(0, g.floor() as u8, 20));
-      if g + g_increment <= 255.0 {
-        g += g_increment;
+    if let Some(timed) = &mut self.speed_effect {
+      timed.ticks_left = timed.ticks_left.saturating_sub(1);
+      if timed.ticks_left == 0 {
+        self.speed_effect = None;
       }
-      canvas
-        .fill_rect(Rect::new(
-          self.positions[i].0 as i32 + self.animation.offsets[i].0 as i32,
-          self.positions[i].1 as i32 + self.animation.offsets[i].1 as i32,
-          game_state.box_size,
-          game_state.box_size,
-        ))
-        .unwrap();
     }
+
+    self.ticks_until_step = self.ticks_until_step.saturating_sub(1);
+    if self.ticks_until_step == 0 {
+      self.ticks_until_step = self.step_ticks();
+      self.step(game_state);
+    }
+  }
+
+  fn step(&mut self, game_state: &GameState) {
+    if let Some(direction) = self.turns.pop_front() {
+      self.direction = direction;
+    }
+
+    let next = match Self::next_cell(game_state, self.direction, self.position()) {
+      Some(next) => Some(next),
+      None => match game_state.rules.edges {
+        EdgeMode::Bounce => self.bounce(game_state),
+        _ => None,
+      },
+    };
+
+    // Ran into the edge of the board, or a wall
+    let Some(next) = next.filter(|cell| !game_state.walls.contains(cell)) else {
+      self.alive = false;
+      return;
+    };
+    self.positions.push(next);
+
+    // Don't just grow forever (and drop whatever's been lost to poison)
+    if self.positions.len() > self.len as usize {
+      let excess = self.positions.len() - self.len as usize;
+      self.positions.drain(..excess);
+    }
+
+    if self.collided_with_self() {
+      self.alive = false;
+    }
+
+    self.animation.tick(self.positions.as_slice());
   }
 }
 
 impl Into<SnekData> for &Snek {
   fn into(self) -> SnekData {
     SnekData {
-      direction: self.direction.clone(),
+      direction: self.direction,
       positions: self.positions.clone(),
       len: self.len,
-      tick_speed_ms: self.tick_speed_ms,
+      speed: self.speed,
+      speed_effect: self.speed_effect,
+      alive: self.alive,
+      score: self.score,
+      hunger: self.hunger,
+      bot: self.bot,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::world::Rules;
+
+  use std::collections::{HashMap, HashSet};
+
+  fn board(edges: EdgeMode) -> GameState {
+    GameState {
+      cols: 8,
+      rows: 6,
+      rules: Rules {
+        edges,
+        ..Rules::default()
+      },
+      walls: HashSet::new(),
+      portals: HashMap::new(),
+    }
+  }
+
+  /// A snek lying along `positions` (tail first), headed `direction`
+  fn laid_out(direction: Direction, positions: &[(u32, u32)]) -> Snek {
+    Snek::load(SnekData {
+      direction,
+      positions: positions.to_vec(),
+      len: positions.len() as u32,
+      speed: SpeedCurve::default(),
+      speed_effect: None,
+      alive: true,
+      score: 0,
+      hunger: 0,
+      bot: None,
+    })
+  }
+
+  /// Ticks until the snek has moved once
+  fn step(snek: &mut Snek, game_state: &GameState) {
+    for _ in 0..snek.ticks_until_step() {
+      snek.tick(game_state);
+    }
+  }
+
+  #[test]
+  fn drops_reversals_and_turns_past_the_cap() {
+    let mut snek = Snek::new(4, 3, Direction::Up, SpeedCurve::default());
+    snek.turn(Direction::Down);
+    snek.turn(Direction::Up);
+    assert_eq!(snek.heading(), Direction::Up);
+
+    snek.turn(Direction::Left);
+    // Straight back the way the last queued turn goes
+    snek.turn(Direction::Right);
+    snek.turn(Direction::Up);
+    snek.turn(Direction::Right);
+    assert_eq!(snek.turns.len(), MAX_QUEUED_TURNS);
+    snek.turn(Direction::Up);
+    assert_eq!(snek.heading(), Direction::Right);
+
+    let game_state = board(EdgeMode::Wrap);
+    step(&mut snek, &game_state);
+    assert_eq!(snek.position(), (3, 3));
+  }
+
+  #[test]
+  fn speeds_up_along_the_curve() {
+    let linear = SpeedCurve::default();
+    assert_eq!(linear.ticks_at(START_LEN), 8);
+    assert_eq!(linear.ticks_at(START_LEN + 4), 8);
+    assert_eq!(linear.ticks_at(START_LEN + 5), 7);
+    assert_eq!(linear.ticks_at(START_LEN + 20), 4);
+    assert_eq!(linear.ticks_at(1000), 4);
+
+    let sqrt = SpeedCurve {
+      shape: CurveShape::SquareRoot,
+      ..SpeedCurve::default()
+    };
+    assert_eq!(sqrt.ticks_at(START_LEN + 5), 7);
+    assert_eq!(sqrt.ticks_at(START_LEN + 15), 7);
+    assert_eq!(sqrt.ticks_at(START_LEN + 20), 6);
+
+    let degenerate = SpeedCurve {
+      floor_ticks: 0,
+      growth_per_step: 0,
+      ..SpeedCurve::default()
+    };
+    assert_eq!(degenerate.ticks_at(1000), 1);
+  }
+
+  #[test]
+  fn wraps_around_the_edges() {
+    let mut snek = Snek::new(2, 0, Direction::Up, SpeedCurve::default());
+    step(&mut snek, &board(EdgeMode::Wrap));
+    assert!(snek.alive);
+    assert_eq!(snek.position(), (2, 5));
+  }
+
+  #[test]
+  fn dies_on_solid_edges() {
+    let mut snek = Snek::new(2, 0, Direction::Up, SpeedCurve::default());
+    step(&mut snek, &board(EdgeMode::Walls));
+    assert!(!snek.alive);
+    assert_eq!(snek.position(), (2, 0));
+  }
+
+  #[test]
+  fn dies_running_into_a_wall() {
+    let mut game_state = board(EdgeMode::Wrap);
+    game_state.walls.insert((3, 2));
+    let mut snek = Snek::new(2, 2, Direction::Right, SpeedCurve::default());
+    step(&mut snek, &game_state);
+    assert!(!snek.alive);
+  }
+
+  #[test]
+  fn bounces_along_the_edge_away_from_its_body() {
+    let game_state = board(EdgeMode::Bounce);
+
+    let mut snek = Snek::new(2, 0, Direction::Up, SpeedCurve::default());
+    step(&mut snek, &game_state);
+    assert!(snek.alive);
+    assert_eq!(snek.position(), (3, 0));
+    assert_eq!(snek.heading(), Direction::Right);
+
+    // Clockwise would be back into its own body, so it goes the other way
+    let mut snek = laid_out(Direction::Up, &[(5, 0), (4, 0), (4, 1), (3, 1), (3, 0)]);
+    step(&mut snek, &game_state);
+    assert!(snek.alive);
+    assert_eq!(snek.position(), (2, 0));
+    assert_eq!(snek.heading(), Direction::Left);
+  }
+
+  #[test]
+  fn comes_out_of_the_other_end_of_a_portal() {
+    let mut game_state = board(EdgeMode::Walls);
+    game_state.portals.insert((2, 1), (6, 4));
+    game_state.portals.insert((6, 4), (2, 1));
+
+    let mut snek = Snek::new(2, 2, Direction::Up, SpeedCurve::default());
+    step(&mut snek, &game_state);
+    assert_eq!(snek.position(), (6, 4));
+    step(&mut snek, &game_state);
+    assert_eq!(snek.position(), (6, 3));
+    assert!(snek.alive);
+  }
+
+  #[test]
+  fn dies_running_into_itself() {
+    let mut snek = laid_out(Direction::Right, &[(4, 2), (3, 2), (2, 2), (2, 1), (3, 1)]);
+    snek.turn(Direction::Down);
+    step(&mut snek, &board(EdgeMode::Wrap));
+    assert!(snek.collided_with_self());
+    assert!(!snek.alive);
+
+    // Moving into the cell the tail's just left is fine
+    let mut snek = laid_out(Direction::Right, &[(3, 2), (2, 2), (2, 1), (3, 1)]);
+    snek.turn(Direction::Down);
+    step(&mut snek, &board(EdgeMode::Wrap));
+    assert!(snek.alive);
+  }
+
+  #[test]
+  fn loads_a_snek_that_has_just_eaten_poison() {
+    let mut snek = laid_out(Direction::Right, &[(0, 2), (1, 2), (2, 2), (3, 2), (4, 2), (5, 2)]);
+    snek.eat(AppleKind::Poison, 1);
+    assert!(snek.positions().len() > snek.len as usize);
+
+    let snek = Snek::load((&snek).into());
+    assert_eq!(snek.animation_offsets().len(), snek.positions().len());
+  }
+
+  #[test]
+  fn grows_by_five_and_scores_extra_for_golden_apples() {
+    let mut snek = Snek::new(4, 3, Direction::Up, SpeedCurve::default());
+    snek.eat(AppleKind::Golden, 2);
+    assert_eq!(snek.len, START_LEN + 5);
+    assert_eq!(snek.score, 20);
+  }
+
+  #[test]
+  fn shrinks_on_poison_but_not_below_the_start() {
+    let mut snek = laid_out(Direction::Right, &[(0, 2), (1, 2), (2, 2), (3, 2), (4, 2), (5, 2)]);
+    snek.score = 4;
+    snek.eat(AppleKind::Poison, 1);
+    assert_eq!(snek.len, START_LEN);
+    assert_eq!(snek.score, 3);
+    snek.eat(AppleKind::Poison, 1);
+    assert_eq!(snek.len, START_LEN);
+
+    step(&mut snek, &board(EdgeMode::Wrap));
+    assert_eq!(snek.positions(), &[(4, 2), (5, 2), (6, 2)]);
+  }
+
+  #[test]
+  fn speeds_up_and_slows_down_for_a_while() {
+    let game_state = board(EdgeMode::Wrap);
+    for (kind, ticks) in [(AppleKind::Pepper, 5), (AppleKind::Ice, 12)] {
+      let mut snek = Snek::new(4, 3, Direction::Up, SpeedCurve::default());
+      snek.eat(kind, 1);
+      assert_eq!(snek.step_ticks(), ticks);
+
+      for _ in 0..SPEED_EFFECT_SECONDS * TICKS_PER_SECOND {
+        snek.tick(&game_state);
+      }
+      assert!(snek.speed_effect.is_none());
+      assert_eq!(snek.step_ticks(), 8);
     }
   }
 }
@@ -0,0 +1,132 @@
+use snek::bot::Bot;
+use snek::controller::SnekController;
+use snek::snek::Direction;
+use snek::world::World;
+
+use std::collections::VecDeque;
+
+/// Where a press on this machine came from
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Device {
+  /// One player's keys on the keyboard
+  Keyboard(usize),
+  /// The gamepad in this slot
+  Gamepad(usize),
+  /// The touch screen, which counts as the first player's keyboard
+  Touch,
+}
+
+/// A snek controller that hears about presses on this machine's devices. Those that aren't steered
+/// by hand don't have to listen.
+pub trait Steering: SnekController {
+  fn press(&mut self, _device: Device, _direction: Direction) {}
+}
+
+/// Presses that have been made and not yet handed over, one of which is handed over each tick
+#[derive(Default)]
+struct Presses(VecDeque<Direction>);
+
+impl Presses {
+  fn next(&mut self) -> Option<Direction> {
+    self.0.pop_front()
+  }
+}
+
+/// Steered with one player's keys, or with anyone's when it's `None`
+pub struct Keyboard {
+  player: Option<usize>,
+  presses: Presses,
+}
+
+impl Keyboard {
+  pub fn new(player: Option<usize>) -> Self {
+    Self {
+      player,
+      presses: Presses::default(),
+    }
+  }
+}
+
+impl SnekController for Keyboard {
+  fn next_direction(&mut self, _world: &World, _snek: usize) -> Option<Direction> {
+    self.presses.next()
+  }
+}
+
+impl Steering for Keyboard {
+  fn press(&mut self, device: Device, direction: Direction) {
+    let heard = match device {
+      Device::Keyboard(player) => self.player.is_none_or(|ours| ours == player),
+      Device::Touch => self.player.is_none_or(|ours| ours == 0),
+      Device::Gamepad(_) => false,
+    };
+    if heard {
+      self.presses.0.push_back(direction);
+    }
+  }
+}
+
+/// Steered with the gamepad in one slot, or with any gamepad when it's `None`
+pub struct Gamepad {
+  slot: Option<usize>,
+  presses: Presses,
+}
+
+impl Gamepad {
+  pub fn new(slot: Option<usize>) -> Self {
+    Self {
+      slot,
+      presses: Presses::default(),
+    }
+  }
+}
+
+impl SnekController for Gamepad {
+  fn next_direction(&mut self, _world: &World, _snek: usize) -> Option<Direction> {
+    self.presses.next()
+  }
+}
+
+impl Steering for Gamepad {
+  fn press(&mut self, device: Device, direction: Direction) {
+    if let Device::Gamepad(slot) = device {
+      if self.slot.is_none_or(|ours| ours == slot) {
+        self.presses.0.push_back(direction);
+      }
+    }
+  }
+}
+
+impl Steering for Bot {}
+
+/// The controllers for each snek in a round played on this machine, paired with the snek they
+/// steer. Bots steer themselves, and each player gets their own keys and gamepad, unless there's
+/// only one player, whose snek is steered with everything.
+pub fn controllers(world: &World) -> Vec<(usize, Box<dyn Steering>)> {
+  let players = world.sneks.iter().filter(|snek| snek.bot.is_none()).count();
+  let mut controllers: Vec<(usize, Box<dyn Steering>)> = Vec::new();
+  for (n, snek) in world.sneks.iter().enumerate() {
+    match snek.bot {
+      Some(settings) => {
+        let seed = world.seed.wrapping_add(n as u64);
+        controllers.push((n, Box::new(Bot::new(settings, seed))));
+      }
+      None if players == 1 => controllers.extend(online_controllers(n)),
+      None => {
+        controllers.push((n, Box::new(Keyboard::new(Some(n)))));
+        controllers.push((n, Box::new(Gamepad::new(Some(n)))));
+      }
+    }
+  }
+  controllers
+}
+
+/// The controllers for a round shared across the network, where this machine steers only
+/// `player`'s snek, with everything. It's whichever snek the other end said, whether or not this
+/// machine's copy of the round has caught up with it yet.
+pub fn online_controllers(player: usize) -> Vec<(usize, Box<dyn Steering>)> {
+  vec![
+    (player, Box::new(Keyboard::new(None))),
+    (player, Box::new(Gamepad::new(None))),
+  ]
+}
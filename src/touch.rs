@@ -0,0 +1,117 @@
+use crate::bindings::Action;
+
+use sdl2::event::Event;
+use sdl2::rect::Rect;
+
+/// How far a finger has to move to count as a swipe, as a fraction of the window's shorter side
+pub static DEFAULT_SWIPE_THRESHOLD: f32 = 0.05;
+
+/// Turns touches into swipes, which steer, and taps, which pause. Only one finger is followed at a
+/// time; any others that land while it's down are ignored.
+pub struct Touch {
+  pub swipe_threshold: f32,
+  /// Whether to show a D-pad in the corner of the screen, for players who'd rather not swipe
+  pub dpad: bool,
+  finger: Option<i64>,
+  /// Where the current swipe started, in pixels
+  swipe_from: (f32, f32),
+  swiped: bool,
+}
+
+impl Touch {
+  pub fn new(swipe_threshold: f32, dpad: bool) -> Self {
+    Self {
+      swipe_threshold,
+      dpad,
+      finger: None,
+      swipe_from: (0.0, 0.0),
+      swiped: false,
+    }
+  }
+
+  /// Handles finger events on a window of the given size, and returns what the finger asked for
+  pub fn process_event(
+    &mut self,
+    event: &Event,
+    window_width: u32,
+    window_height: u32,
+  ) -> Option<Action> {
+    let (width, height) = (window_width as f32, window_height as f32);
+    match *event {
+      Event::FingerDown {
+        finger_id, x, y, ..
+      } if self.finger.is_none() => {
+        let (x, y) = (x * width, y * height);
+        self.finger = Some(finger_id);
+        self.swipe_from = (x, y);
+        self.swiped = false;
+
+        // D-pad buttons turn as soon as they're pressed, and don't start a swipe
+        if self.dpad {
+          let pressed = dpad_buttons(window_width, window_height)
+            .into_iter()
+            .find(|(_, button)| button.contains_point((x as i32, y as i32)));
+          if let Some((action, _)) = pressed {
+            self.swiped = true;
+            return Some(action);
+          }
+        }
+        None
+      }
+      Event::FingerMotion {
+        finger_id, x, y, ..
+      } if self.finger == Some(finger_id) => {
+        let (x, y) = (x * width, y * height);
+        let (dx, dy) = (x - self.swipe_from.0, y - self.swipe_from.1);
+        if dx.abs().max(dy.abs()) < self.swipe_threshold * width.min(height) {
+          return None;
+        }
+
+        // Each swipe starts where the last one left off, so one finger can steer around corners
+        // without being lifted
+        self.swipe_from = (x, y);
+        self.swiped = true;
+        Some(if dx.abs() > dy.abs() {
+          if dx < 0.0 {
+            Action::Left
+          } else {
+            Action::Right
+          }
+        } else if dy < 0.0 {
+          Action::Up
+        } else {
+          Action::Down
+        })
+      }
+      Event::FingerUp { finger_id, .. } if self.finger == Some(finger_id) => {
+        self.finger = None;
+        (!self.swiped).then_some(Action::Pause)
+      }
+      _ => None,
+    }
+  }
+}
+
+/// The on-screen D-pad's buttons: a plus sign in the bottom right corner of the window
+pub fn dpad_buttons(window_width: u32, window_height: u32) -> [(Action, Rect); 4] {
+  let size = window_width.min(window_height) / 12;
+  let margin = size as i32 / 2;
+  let (center_x, center_y) = (
+    window_width as i32 - margin - size as i32 * 3 / 2,
+    window_height as i32 - margin - size as i32 * 3 / 2,
+  );
+  let button = |dx: i32, dy: i32| {
+    Rect::from_center(
+      (center_x + dx * size as i32, center_y + dy * size as i32),
+      size,
+      size,
+    )
+  };
+
+  [
+    (Action::Up, button(0, -1)),
+    (Action::Down, button(0, 1)),
+    (Action::Left, button(-1, 0)),
+    (Action::Right, button(1, 0)),
+  ]
+}
@@ -0,0 +1,595 @@
+use crate::apple::{Apple, AppleTable};
+use crate::file::{GameData, SAVE_VERSION};
+use crate::level::Level;
+use crate::mode::GameMode;
+use crate::snek::{Snek, SpeedCurve};
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+/// The board, measured in cells, and the rules it's played by
+pub struct GameState {
+  pub cols: u32,
+  pub rows: u32,
+  pub rules: Rules,
+  /// Cells that kill any snek that runs into them
+  pub walls: HashSet<(u32, u32)>,
+  /// Each portal cell, and the cell at the other end of it
+  pub portals: HashMap<(u32, u32), (u32, u32)>,
+}
+
+impl GameState {
+  /// Whether leaving one edge of the board brings you back in on the other
+  pub fn wraps(&self) -> bool {
+    self.rules.edges == EdgeMode::Wrap
+  }
+
+  /// Where something that steps onto `cell` actually ends up, which is only somewhere else if
+  /// `cell` is a portal
+  pub fn through_portal(&self, cell: (u32, u32)) -> (u32, u32) {
+    self.portals.get(&cell).copied().unwrap_or(cell)
+  }
+
+  /// A random cell on the board that isn't in `occupied`, or `None` if the board is full
+  pub fn free_cell(
+    &self,
+    occupied: &HashSet<(u32, u32)>,
+    rng: &mut impl Rng,
+  ) -> Option<(u32, u32)> {
+    let cols = self.cols;
+    let free: Vec<(u32, u32)> = (0..self.rows)
+      .flat_map(|y| (0..cols).map(move |x| (x, y)))
+      .filter(|cell| {
+        !occupied.contains(cell) && !self.walls.contains(cell) && !self.portals.contains_key(cell)
+      })
+      .collect();
+    free.choose(rng).copied()
+  }
+
+  /// How many steps it takes to get from `a` to `b`, ignoring walls, counting shortcuts across
+  /// the edges if the board wraps
+  pub fn distance(&self, a: (u32, u32), b: (u32, u32)) -> u32 {
+    let along = |a: u32, b: u32, size: u32| {
+      let d = a.abs_diff(b);
+      if self.wraps() {
+        d.min(size - d)
+      } else {
+        d
+      }
+    };
+    along(a.0, b.0, self.cols) + along(a.1, b.1, self.rows)
+  }
+}
+
+/// What happens when a snek's head runs into another snek
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum CollisionRule {
+  /// Sneks slide through each other untouched
+  PassThrough,
+  /// Running into a body kills you, and meeting head-on kills both sneks
+  #[default]
+  BothDie,
+  /// Running into a body kills you, and meeting head-on kills only the shorter snek
+  LongerWins,
+}
+
+impl FromStr for CollisionRule {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "pass-through" => Ok(Self::PassThrough),
+      "both-die" => Ok(Self::BothDie),
+      "longer-wins" => Ok(Self::LongerWins),
+      _ => Err(format!(
+        "unknown collision rule `{}` (expected pass-through, both-die or longer-wins)",
+        s
+      )),
+    }
+  }
+}
+
+/// What happens when a snek reaches the edge of the board
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum EdgeMode {
+  /// Come back in on the opposite edge
+  #[default]
+  Wrap,
+  /// The edges are solid, and running into one kills you
+  Walls,
+  /// The edges are solid, but sneks glance off of them, turning to run along the wall
+  Bounce,
+}
+
+impl FromStr for EdgeMode {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "wrap" => Ok(Self::Wrap),
+      "walls" => Ok(Self::Walls),
+      "bounce" => Ok(Self::Bounce),
+      _ => Err(format!(
+        "unknown edge mode `{}` (expected wrap, walls or bounce)",
+        s
+      )),
+    }
+  }
+}
+
+/// The settings a round is played with, kept for the whole round and saved alongside it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct Rules {
+  #[serde(default)]
+  pub collisions: CollisionRule,
+  #[serde(default)]
+  pub edges: EdgeMode,
+  /// How new sneks speed up as they grow
+  #[serde(default)]
+  pub speed: SpeedCurve,
+  #[serde(default)]
+  pub mode: GameMode,
+  /// How often each kind of apple grows
+  #[serde(default)]
+  pub apples: AppleTable,
+  /// Seconds an apple lasts before it goes off and grows back somewhere else, if apples go off
+  #[serde(default)]
+  pub apple_lifetime: Option<u32>,
+  /// Whether apples run away from the nearest snek
+  #[serde(default)]
+  pub wandering_apples: bool,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+pub enum Status {
+  #[default]
+  Playing,
+  GameOver,
+  /// There's nowhere left on the board for an apple to go
+  Won,
+  /// The clock ran out on a timed round
+  TimeUp,
+}
+
+/// All of the game's rules and objects, with no notion of windows, fonts or input devices
+pub struct World {
+  pub game_state: GameState,
+  /// The map this round is played on, kept around so that the round can be restarted
+  pub level: Level,
+
+  pub sneks: Vec<Snek>,
+  pub apples: Vec<Apple>,
+
+  pub status: Status,
+
+  /// The seed this round started from; all of the game's randomness comes from `rng`
+  pub seed: u64,
+  pub rng: ChaCha12Rng,
+
+  /// How many game ticks this round has been running for
+  pub ticks: u64,
+}
+
+/// The simulation always advances in steps of this fixed size, no matter the frame rate
+pub static TICKS_PER_SECOND: u32 = 120;
+
+/// A seed for when the player hasn't asked for a specific one
+pub fn random_seed() -> u64 {
+  rand::random()
+}
+
+impl World {
+  /// A fresh round on `level` for `players` sneks
+  pub fn new(level: Level, rules: Rules, players: usize, seed: u64) -> Self {
+    let mut world = Self {
+      game_state: GameState {
+        cols: level.cols,
+        rows: level.rows,
+        rules,
+        walls: level.walls.iter().copied().collect(),
+        portals: level.portal_links(),
+      },
+
+      sneks: (0..players)
+        .map(|n| {
+          let spawn = level.spawn(n, players);
+          Snek::new(spawn.x, spawn.y, spawn.direction, rules.speed)
+        })
+        .collect(),
+      apples: Vec::new(),
+
+      status: Status::Playing,
+
+      seed,
+      rng: ChaCha12Rng::seed_from_u64(seed),
+
+      ticks: 0,
+
+      level,
+    };
+
+    for &(x, y) in &world.level.apples {
+      let kind = rules.apples.pick(&mut world.rng);
+      world.apples.push(Apple::new(x, y, kind));
+    }
+
+    // Levels that don't place their own apples get one somewhere random
+    if world.apples.is_empty() {
+      let occupied = world.occupied_cells();
+      if let Some((x, y)) = world.game_state.free_cell(&occupied, &mut world.rng) {
+        let kind = rules.apples.pick(&mut world.rng);
+        world.apples.push(Apple::new(x, y, kind));
+      }
+    }
+
+    world
+  }
+
+  pub fn load(game_data: GameData) -> Self {
+    let mut rng = ChaCha12Rng::seed_from_u64(game_data.seed);
+    rng.set_word_pos(game_data.rng_word_pos);
+
+    // Saves from before levels were played on an empty board
+    let level = game_data
+      .level
+      .unwrap_or_else(|| Level::empty(game_data.cols, game_data.rows));
+
+    Self {
+      game_state: GameState {
+        cols: game_data.cols,
+        rows: game_data.rows,
+        rules: game_data.rules,
+        walls: level.walls.iter().copied().collect(),
+        portals: level.portal_links(),
+      },
+      level,
+
+      sneks: game_data
+        .sneks
+        .into_iter()
+        .map(Snek::load)
+        .collect(),
+      apples: game_data.apples,
+
+      status: game_data.status,
+
+      seed: game_data.seed,
+      rng,
+
+      ticks: game_data.ticks,
+    }
+  }
+
+  /// Throw away this round and start a fresh one on the same level, with the same rules and players.
+  /// The new seed is drawn from this round's RNG, so a whole session replays from its first seed.
+  pub fn restart(&mut self) {
+    let bots: Vec<_> = self.sneks.iter().map(|snek| snek.bot).collect();
+    *self = Self::new(
+      self.level.clone(),
+      self.game_state.rules,
+      self.sneks.len(),
+      self.rng.gen(),
+    );
+    for (snek, bot) in self.sneks.iter_mut().zip(bots) {
+      snek.bot = bot;
+    }
+  }
+
+  /// Advances the round by one game tick
+  pub fn tick(&mut self) {
+    if self.status != Status::Playing {
+      return;
+    }
+    self.ticks += 1;
+
+    // Check interactions between game objects
+    //   If a snake has eaten an apple:
+    'apples: for apple in &mut self.apples {
+      if !apple.eaten {
+        for snek in &mut self.sneks {
+          if snek.alive && snek.position() == (apple.x, apple.y) {
+            let points = self.game_state.rules.mode.apple_points();
+            snek.eat(apple.kind, points);
+            apple.eaten = true;
+            continue 'apples;
+          }
+        }
+      }
+    }
+
+    for snek in &mut self.sneks {
+      snek.tick(&self.game_state);
+    }
+
+    self.resolve_collisions();
+
+    let mode = self.game_state.rules.mode;
+    mode.tick(self);
+
+    let heads: Vec<(u32, u32)> = self
+      .sneks
+      .iter()
+      .filter(|snek| snek.alive)
+      .map(|snek| snek.position())
+      .collect();
+    let mut occupied = self.occupied_cells();
+    for apple in &mut self.apples {
+      let placed = apple.tick(&self.game_state, &heads, &mut self.rng, &mut occupied);
+      if !placed && self.status == Status::Playing {
+        self.status = Status::Won;
+      }
+    }
+
+    if self.status == Status::Playing && self.sneks.iter().all(|snek| !snek.alive) {
+      self.status = Status::GameOver;
+    }
+  }
+
+  /// A fingerprint of everything that decides how the rest of the round plays out, for checking
+  /// that two copies of a round haven't drifted apart. It's the same on every machine and every
+  /// build, so peers built with different compilers can still compare notes.
+  pub fn state_hash(&self) -> u64 {
+    let mut hash = Fnv::default();
+    hash.u64(self.ticks);
+    hash.u64(self.status as u64);
+    hash.u128(self.rng.get_word_pos());
+    for snek in &self.sneks {
+      hash.u64(snek.positions().len() as u64);
+      for &(x, y) in snek.positions() {
+        hash.u32(x);
+        hash.u32(y);
+      }
+      hash.u32(snek.len);
+      hash.u64(snek.alive as u64);
+      hash.u32(snek.score);
+      hash.u32(snek.step_ticks());
+    }
+    for apple in &self.apples {
+      hash.u32(apple.x);
+      hash.u32(apple.y);
+      hash.u64(apple.kind as u64);
+      hash.u64(apple.eaten as u64);
+    }
+    hash.0
+  }
+
+  /// Every cell that something is sitting in: sneks (living or not) and uneaten apples
+  pub fn occupied_cells(&self) -> HashSet<(u32, u32)> {
+    let snek_cells = self.sneks.iter().flat_map(|snek| snek.positions().iter().copied());
+    let apple_cells = self
+      .apples
+      .iter()
+      .filter(|apple| !apple.eaten)
+      .map(|apple| (apple.x, apple.y));
+
+    snek_cells.chain(apple_cells).collect()
+  }
+
+  /// Kill every snek whose head has run into another snek, according to the collision rule.
+  /// Deaths are decided for everyone before any are applied, so the order of `sneks` doesn't matter.
+  fn resolve_collisions(&mut self) {
+    let rule = self.game_state.rules.collisions;
+    if rule == CollisionRule::PassThrough {
+      return;
+    }
+
+    let mut killed = vec![false; self.sneks.len()];
+    for (i, snek) in self.sneks.iter().enumerate() {
+      if !snek.alive {
+        continue;
+      }
+
+      let head = snek.position();
+      for (j, other) in self.sneks.iter().enumerate() {
+        if i == j {
+          continue;
+        }
+
+        if other.alive && other.position() == head {
+          // Head-on; the other snek gets judged on its own turn through the loop
+          killed[i] |= match rule {
+            CollisionRule::LongerWins => snek.len <= other.len,
+            _ => true,
+          };
+        } else if other.positions().contains(&head) {
+          // Into the body of another snek (or whatever's left of it)
+          killed[i] = true;
+        }
+      }
+    }
+
+    for (snek, killed) in self.sneks.iter_mut().zip(killed) {
+      if killed {
+        snek.alive = false;
+      }
+    }
+  }
+}
+
+/// 64-bit FNV-1a, which unlike the standard library's hasher is pinned down byte for byte
+struct Fnv(u64);
+
+impl Default for Fnv {
+  fn default() -> Self {
+    Self(0xcbf2_9ce4_8422_2325)
+  }
+}
+
+impl Fnv {
+  fn bytes(&mut self, bytes: &[u8]) {
+    for &byte in bytes {
+      self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+    }
+  }
+
+  fn u32(&mut self, n: u32) {
+    self.bytes(&n.to_le_bytes());
+  }
+
+  fn u64(&mut self, n: u64) {
+    self.bytes(&n.to_le_bytes());
+  }
+
+  fn u128(&mut self, n: u128) {
+    self.bytes(&n.to_le_bytes());
+  }
+}
+
+impl From<&World> for GameData {
+  fn from(world: &World) -> Self {
+    GameData {
+      version: SAVE_VERSION,
+      cols: world.game_state.cols,
+      rows: world.game_state.rows,
+      sneks: world.sneks.iter().map(|s| s.into()).collect(),
+      apples: world.apples.clone(),
+      status: world.status,
+      rules: world.game_state.rules,
+      seed: world.seed,
+      rng_word_pos: world.rng.get_word_pos(),
+      ticks: world.ticks,
+      level: Some(world.level.clone()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::apple::AppleKind;
+  use crate::snek::{Direction, START_LEN};
+
+  /// A round on an empty `cols` x `rows` board with sneks lying along the given cells (tail first),
+  /// and no apples
+  fn laid_out(cols: u32, rows: u32, rules: Rules, sneks: &[(&[(u32, u32)], Direction)]) -> World {
+    let world = World::new(Level::empty(cols, rows), rules, sneks.len(), 1);
+    let mut game_data = GameData::from(&world);
+    for (snek, &(positions, direction)) in game_data.sneks.iter_mut().zip(sneks) {
+      snek.positions = positions.to_vec();
+      snek.len = positions.len() as u32;
+      snek.direction = direction;
+    }
+    game_data.apples.clear();
+    World::load(game_data)
+  }
+
+  /// Ticks until the first snek has moved once
+  fn step(world: &mut World) {
+    for _ in 0..world.sneks[0].ticks_until_step() {
+      world.tick();
+    }
+  }
+
+  #[test]
+  fn places_apples_the_same_way_from_the_same_seed() {
+    let apples = |seed| {
+      let mut world = World::new(Level::empty(20, 15), Rules::default(), 1, seed);
+      let mut cells = Vec::new();
+      for _ in 0..20 {
+        let apple = &mut world.apples[0];
+        cells.push((apple.x, apple.y, apple.kind));
+        apple.eaten = true;
+        world.tick();
+      }
+      cells
+    };
+
+    assert_eq!(apples(7), apples(7));
+    assert_ne!(apples(7), apples(8));
+  }
+
+  #[test]
+  fn meeting_head_on_kills_both_sneks() {
+    let mut world = laid_out(
+      10,
+      10,
+      Rules::default(),
+      &[
+        (&[(1, 5), (2, 5), (3, 5)], Direction::Right),
+        (&[(9, 5), (8, 5), (7, 5)], Direction::Left),
+      ],
+    );
+    step(&mut world);
+    step(&mut world);
+    assert_eq!(world.sneks[0].position(), (5, 5));
+    assert!(!world.sneks[0].alive);
+    assert!(!world.sneks[1].alive);
+    assert_eq!(world.status, Status::GameOver);
+  }
+
+  #[test]
+  fn meeting_head_on_spares_the_longer_snek() {
+    let rules = Rules {
+      collisions: CollisionRule::LongerWins,
+      ..Rules::default()
+    };
+    let mut world = laid_out(
+      10,
+      10,
+      rules,
+      &[
+        (&[(0, 5), (1, 5), (2, 5), (3, 5)], Direction::Right),
+        (&[(9, 5), (8, 5), (7, 5)], Direction::Left),
+      ],
+    );
+    step(&mut world);
+    step(&mut world);
+    assert_eq!(world.sneks[0].position(), (5, 5));
+    assert!(world.sneks[0].alive);
+    assert!(!world.sneks[1].alive);
+  }
+
+  #[test]
+  fn running_into_another_snek_kills_only_the_one_that_hit() {
+    let mut world = laid_out(
+      10,
+      10,
+      Rules::default(),
+      &[
+        (&[(2, 3), (3, 3), (4, 3)], Direction::Right),
+        (&[(5, 1), (5, 2), (5, 3)], Direction::Down),
+      ],
+    );
+    step(&mut world);
+    assert!(!world.sneks[0].alive);
+    assert!(world.sneks[1].alive);
+    assert_eq!(world.status, Status::Playing);
+
+    let rules = Rules {
+      collisions: CollisionRule::PassThrough,
+      ..Rules::default()
+    };
+    let mut world = laid_out(
+      10,
+      10,
+      rules,
+      &[
+        (&[(2, 3), (3, 3), (4, 3)], Direction::Right),
+        (&[(5, 1), (5, 2), (5, 3)], Direction::Down),
+      ],
+    );
+    step(&mut world);
+    assert!(world.sneks[0].alive);
+    assert!(world.sneks[1].alive);
+  }
+
+  #[test]
+  fn filling_the_board_wins() {
+    let snek: &[(u32, u32)] = &[(0, 0), (1, 0), (2, 0)];
+    let mut world = laid_out(4, 1, Rules::default(), &[(snek, Direction::Right)]);
+    world.apples.push(Apple::new(3, 0, AppleKind::Red));
+
+    for _ in 0..100 {
+      if world.status != Status::Playing {
+        break;
+      }
+      world.tick();
+    }
+    assert_eq!(world.status, Status::Won);
+    assert_eq!(world.sneks[0].len, START_LEN + 2);
+  }
+}